@@ -12,8 +12,16 @@ O(V^2) maximum swaps and O(V) for the check so this simple solution is O(V^3) in
 A more sophisticated solution would be to consider the rules as a directed graph and then find a topological ordering on the graph.
 You can do this in O(V + E) by running a DFS and then taking the nodes as they are exited in reverse order.
 
+That's now RuleGraph::order, using Kahn's algorithm instead, on the rules between the pages of each update. The full set of
+rules has cycles (RuleGraph::cycle) so only the induced subgraph can be sorted. Kahn's algorithm also tells us if the order
+is unique: it is when there's only ever one page ready to go next. The ready pages are kept in a heap by their position in
+the update, so a correct update is left as it is, at the cost of a log factor.
+
 */
 
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
 fn main() {
     let input = parse(include_str!("../../input/05.txt"));
 
@@ -24,24 +32,59 @@ fn main() {
     let part2 = part2(&input);
     println!("Part 2: {}", part2);
     assert_eq!(part2, 5169);
+
+    let graph = RuleGraph::new(&input.rules);
+    let mut unique = 0;
+    for update in &input.updates {
+        let order = graph.order(update).unwrap();
+        assert_eq!(order.pages, sort_by_swaps(update, &input.rules));
+        if order.unique {
+            unique += 1;
+        }
+    }
+    println!(
+        "Updates with a unique order: {}/{}",
+        unique,
+        input.updates.len()
+    );
+    let pages: Vec<i64> = graph.after.keys().cloned().collect();
+    if let Some(cycle) = graph.cycle(&pages) {
+        println!("Rules have a cycle of {} pages: {:?}", cycle.len(), cycle);
+    }
+
+    // `--explain` lists the rules each rejected update breaks and how to fix it
+    if std::env::args().any(|a| a == "--explain") {
+        for update in &input.updates {
+            let explanation = explain(update, &input, &graph).unwrap();
+            let fixed = apply_moves(update, &explanation.moves);
+            assert!(is_correct_update(&fixed, &input.rules).is_none());
+            if !explanation.broken.is_empty() {
+                print!("{}", explanation.format(update));
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 struct Input {
     rules: Vec<(i64, i64)>,
+    // line number in the input (from 1) of each rule
+    rule_lines: Vec<usize>,
     updates: Vec<Vec<i64>>,
 }
 
 fn parse(input: &str) -> Input {
     let mut rules = Vec::new();
+    let mut rule_lines = Vec::new();
     let mut updates = Vec::new();
-    for line in input.lines().filter(|l| !l.is_empty()) {
+    for (line_number, line) in input.lines().enumerate().filter(|(_, l)| !l.is_empty()) {
         // Rules
         if line.contains("|") {
             let mut parts = line.split('|');
             let x = parts.next().unwrap();
             let y = parts.next().unwrap();
             rules.push((x.parse().unwrap(), y.parse().unwrap()));
+            rule_lines.push(line_number + 1);
         }
         // Updates
         else if line.contains(",") {
@@ -54,7 +97,11 @@ fn parse(input: &str) -> Input {
             updates.push(update);
         }
     }
-    Input { rules, updates }
+    Input {
+        rules,
+        rule_lines,
+        updates,
+    }
 }
 
 fn is_correct_update(update: &Vec<i64>, rules: &Vec<(i64, i64)>) -> Option<(usize, usize)> {
@@ -85,27 +132,282 @@ fn part1(input: &Input) -> i64 {
 }
 
 fn part2(input: &Input) -> i64 {
+    let graph = RuleGraph::new(&input.rules);
     let mut sum = 0;
     for update in &input.updates {
-        let mut update = update.clone();
-        let starts_correct = is_correct_update(&update, &input.rules).is_none();
+        let starts_correct = is_correct_update(update, &input.rules).is_none();
         if starts_correct {
             continue;
         }
-        loop {
-            if let Some((x, y)) = is_correct_update(&update, &input.rules) {
-                update.swap(x, y);
-                // println!("swapped {:?} -> {:?}", x, y);
-            } else {
-                break;
-            }
-        }
+        let update = graph.order(update).unwrap().pages;
         let middle = update[update.len() / 2];
         sum += middle;
     }
     sum
 }
 
+// The original part 2: swap the first broken rule until there aren't any. O(V^3)
+fn sort_by_swaps(update: &[i64], rules: &Vec<(i64, i64)>) -> Vec<i64> {
+    let mut update = update.to_vec();
+    while let Some((x, y)) = is_correct_update(&update, rules) {
+        update.swap(x, y);
+    }
+    update
+}
+
+// edge x -> y for each rule x|y
+#[derive(Debug)]
+struct RuleGraph {
+    after: HashMap<i64, Vec<i64>>,
+    before: HashMap<i64, Vec<i64>>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Ordering {
+    pages: Vec<i64>,
+    // whether this is the only order that follows the rules
+    unique: bool,
+}
+
+impl RuleGraph {
+    fn new(rules: &[(i64, i64)]) -> RuleGraph {
+        let mut after: HashMap<i64, Vec<i64>> = HashMap::new();
+        let mut before: HashMap<i64, Vec<i64>> = HashMap::new();
+        for &(x, y) in rules {
+            after.entry(x).or_default().push(y);
+            before.entry(y).or_default().push(x);
+            after.entry(y).or_default();
+            before.entry(x).or_default();
+        }
+        RuleGraph { after, before }
+    }
+
+    // Edges of the subgraph induced by `pages`
+    fn induced<'a>(
+        &'a self,
+        edges: &'a HashMap<i64, Vec<i64>>,
+        pages: &'a HashSet<i64>,
+        page: i64,
+    ) -> impl Iterator<Item = i64> + 'a {
+        edges
+            .get(&page)
+            .into_iter()
+            .flatten()
+            .cloned()
+            .filter(|p| pages.contains(p))
+    }
+
+    // kahn's algorithm, Err with a cycle if there's no order
+    // ready pages go in update order, so a correct update comes back unchanged
+    fn order(&self, update: &[i64]) -> Result<Ordering, Vec<i64>> {
+        let pages: HashSet<i64> = update.iter().cloned().collect();
+        let position: HashMap<i64, usize> =
+            update.iter().enumerate().map(|(i, &p)| (p, i)).collect();
+        let mut in_degree: HashMap<i64, usize> = update
+            .iter()
+            .map(|&page| (page, self.induced(&self.before, &pages, page).count()))
+            .collect();
+        let mut ready: BinaryHeap<Reverse<usize>> = update
+            .iter()
+            .filter(|page| in_degree[page] == 0)
+            .map(|page| Reverse(position[page]))
+            .collect();
+        let mut order = vec![];
+        let mut unique = true;
+        while let Some(Reverse(k)) = ready.pop() {
+            let page = update[k];
+            if !ready.is_empty() {
+                unique = false;
+            }
+            order.push(page);
+            for next in self.induced(&self.after, &pages, page) {
+                let d = in_degree.get_mut(&next).unwrap();
+                *d -= 1;
+                if *d == 0 {
+                    ready.push(Reverse(position[&next]));
+                }
+            }
+        }
+        if order.len() < update.len() {
+            let left: Vec<i64> = update
+                .iter()
+                .cloned()
+                .filter(|page| in_degree[page] > 0)
+                .collect();
+            return Err(self.cycle(&left).unwrap());
+        }
+        Ok(Ordering {
+            pages: order,
+            unique,
+        })
+    }
+
+    // cycle in the rules between `pages`, each page before the next and the last before the first
+    fn cycle(&self, pages: &[i64]) -> Option<Vec<i64>> {
+        let pages: HashSet<i64> = pages.iter().cloned().collect();
+        // iterative DFS colouring, 1 = on the stack, 2 = done
+        let mut colour: HashMap<i64, u8> = HashMap::new();
+        let mut starts: Vec<i64> = pages.iter().cloned().collect();
+        starts.sort();
+        for start in starts {
+            if colour.contains_key(&start) {
+                continue;
+            }
+            let mut path = vec![start];
+            let mut stack = vec![self.induced(&self.after, &pages, start).collect::<Vec<_>>()];
+            colour.insert(start, 1);
+            while let Some(next) = stack.last_mut() {
+                match next.pop() {
+                    Some(page) => match colour.get(&page) {
+                        Some(1) => {
+                            let k = path.iter().position(|&p| p == page).unwrap();
+                            return Some(path[k..].to_vec());
+                        }
+                        Some(_) => {}
+                        None => {
+                            colour.insert(page, 1);
+                            path.push(page);
+                            stack.push(self.induced(&self.after, &pages, page).collect());
+                        }
+                    },
+                    None => {
+                        colour.insert(path.pop().unwrap(), 2);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+// rule `before|after` on line `line` that an update breaks
+#[derive(Debug, Clone, PartialEq)]
+struct BrokenRule {
+    before: i64,
+    after: i64,
+    line: usize,
+}
+
+// take `page` out and put it back before `before`, or at the end if None
+#[derive(Debug, Clone, PartialEq)]
+struct Move {
+    page: i64,
+    before: Option<i64>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Explanation {
+    broken: Vec<BrokenRule>,
+    moves: Vec<Move>,
+}
+
+impl Explanation {
+    fn format(&self, update: &[i64]) -> String {
+        let pages: Vec<String> = update.iter().map(|p| p.to_string()).collect();
+        let mut out = format!("{} breaks {} rules:\n", pages.join(","), self.broken.len());
+        for rule in &self.broken {
+            out.push_str(&format!(
+                "  line {}: {}|{}\n",
+                rule.line, rule.before, rule.after
+            ));
+        }
+        out.push_str(&format!("fixed with {} moves:\n", self.moves.len()));
+        for m in &self.moves {
+            match m.before {
+                Some(before) => out.push_str(&format!("  move {} before {}\n", m.page, before)),
+                None => out.push_str(&format!("  move {} to the end\n", m.page)),
+            }
+        }
+        out
+    }
+}
+
+// Every rule with both pages in the update, but in the wrong order
+fn broken_rules(update: &[i64], input: &Input) -> Vec<BrokenRule> {
+    let position: HashMap<i64, usize> = update.iter().enumerate().map(|(i, &p)| (p, i)).collect();
+    input
+        .rules
+        .iter()
+        .zip(&input.rule_lines)
+        .filter(|((x, y), _)| match (position.get(x), position.get(y)) {
+            (Some(i), Some(j)) => i > j,
+            _ => false,
+        })
+        .map(|(&(before, after), &line)| BrokenRule {
+            before,
+            after,
+            line,
+        })
+        .collect()
+}
+
+// broken rules and the moves into RuleGraph::order, none if no rules are broken
+// moves are only minimal when the order is unique (true for the whole input)
+fn explain(update: &[i64], input: &Input, graph: &RuleGraph) -> Result<Explanation, Vec<i64>> {
+    let broken = broken_rules(update, input);
+    let target = graph.order(update)?.pages;
+    let rank: HashMap<i64, usize> = target.iter().enumerate().map(|(i, &p)| (p, i)).collect();
+
+    // The pages that stay put are the longest run (not necessarily contiguous) already in target order
+    let ranks: Vec<usize> = update.iter().map(|p| rank[p]).collect();
+    let keep: HashSet<i64> = longest_increasing(&ranks)
+        .into_iter()
+        .map(|k| update[k])
+        .collect();
+
+    // Working back from the end, each moved page goes just before the page that follows it in the target order
+    let mut moves = vec![];
+    for (i, &page) in target.iter().enumerate().rev() {
+        if !keep.contains(&page) {
+            moves.push(Move {
+                page,
+                before: target.get(i + 1).cloned(),
+            });
+        }
+    }
+    Ok(Explanation { broken, moves })
+}
+
+// Indices of a longest strictly increasing subsequence, O(n log n)
+fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    // tails[len] = index of the smallest value ending an increasing subsequence of length len + 1
+    let mut tails: Vec<usize> = vec![];
+    let mut prev = vec![None; values.len()];
+    for (i, &v) in values.iter().enumerate() {
+        let len = tails.partition_point(|&t| values[t] < v);
+        if len > 0 {
+            prev[i] = Some(tails[len - 1]);
+        }
+        if len == tails.len() {
+            tails.push(i);
+        } else {
+            tails[len] = i;
+        }
+    }
+    let mut indices = vec![];
+    let mut k = tails.last().cloned();
+    while let Some(i) = k {
+        indices.push(i);
+        k = prev[i];
+    }
+    indices.reverse();
+    indices
+}
+
+fn apply_moves(update: &[i64], moves: &[Move]) -> Vec<i64> {
+    let mut update = update.to_vec();
+    for m in moves {
+        update.retain(|&p| p != m.page);
+        let k = match m.before {
+            Some(before) => update.iter().position(|&p| p == before).unwrap(),
+            None => update.len(),
+        };
+        update.insert(k, m.page);
+    }
+    update
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -116,4 +418,140 @@ mod tests {
         assert_eq!(part1(&input), 143);
         assert_eq!(part2(&input), 123);
     }
+
+    #[test]
+    fn test_order() {
+        let input = parse(include_str!("../../input/05_example.txt"));
+        let graph = RuleGraph::new(&input.rules);
+        let order = graph.order(&[97, 13, 75, 29, 47]).unwrap();
+        assert_eq!(order.pages, vec![97, 75, 47, 29, 13]);
+        assert!(order.unique);
+        for update in &input.updates {
+            assert_eq!(
+                graph.order(update).unwrap().pages,
+                sort_by_swaps(update, &input.rules)
+            );
+        }
+        // the example rules happen to have no cycle at all
+        let pages: Vec<i64> = graph.after.keys().cloned().collect();
+        assert_eq!(graph.cycle(&pages), None);
+
+        // 1 and 3 can go either way round
+        let graph = RuleGraph::new(&[(1, 2), (3, 2)]);
+        assert_eq!(
+            graph.order(&[2, 3, 1]),
+            Ok(Ordering {
+                pages: vec![3, 1, 2],
+                unique: false
+            })
+        );
+        assert!(graph.order(&[1, 2]).unwrap().unique);
+        // not unique, but already correct, so nothing moves
+        let graph = RuleGraph::new(&[(1, 2)]);
+        assert_eq!(
+            graph.order(&[1, 2, 3]),
+            Ok(Ordering {
+                pages: vec![1, 2, 3],
+                unique: false
+            })
+        );
+        assert_eq!(graph.order(&[3, 2, 1]).unwrap().pages, vec![3, 1, 2]);
+
+        // 1 -> 2 -> 3 -> 1, with 4 before all of them
+        let graph = RuleGraph::new(&[(1, 2), (2, 3), (3, 1), (4, 1), (4, 2)]);
+        let cycle = graph.order(&[4, 3, 2, 1]).unwrap_err();
+        assert_eq!(cycle.len(), 3);
+        for k in 0..3 {
+            let rule = (cycle[k], cycle[(k + 1) % 3]);
+            assert!([(1, 2), (2, 3), (3, 1)].contains(&rule));
+        }
+        // without 3 there's no cycle
+        assert_eq!(graph.order(&[2, 1, 4]).unwrap().pages, vec![4, 1, 2]);
+        assert_eq!(graph.cycle(&[1, 2, 4]), None);
+    }
+
+    #[test]
+    fn test_explain() {
+        let input = parse(include_str!("../../input/05_example.txt"));
+        let graph = RuleGraph::new(&input.rules);
+        assert_eq!(input.rule_lines[0], 1);
+        assert_eq!(input.rule_lines.len(), 21);
+
+        // 97 has to come before 75
+        let explanation = explain(&[75, 97, 47, 61, 53], &input, &graph).unwrap();
+        assert_eq!(
+            explanation.broken,
+            vec![BrokenRule {
+                before: 97,
+                after: 75,
+                line: 16
+            }]
+        );
+        assert_eq!(
+            explanation.moves,
+            vec![Move {
+                page: 75,
+                before: Some(47)
+            }]
+        );
+        assert_eq!(
+            explanation.format(&[75, 97, 47, 61, 53]),
+            "75,97,47,61,53 breaks 1 rules:\n  line 16: 97|75\nfixed with 1 moves:\n  move 75 before 47\n"
+        );
+
+        let explanation = explain(&[97, 13, 75, 29, 47], &input, &graph).unwrap();
+        let broken: Vec<(i64, i64)> = explanation
+            .broken
+            .iter()
+            .map(|rule| (rule.before, rule.after))
+            .collect();
+        assert_eq!(broken, vec![(29, 13), (47, 13), (47, 29), (75, 13)]);
+        // 13 to the end, then 29 before it
+        assert_eq!(explanation.moves.len(), 2);
+        assert_eq!(
+            apply_moves(&[97, 13, 75, 29, 47], &explanation.moves),
+            vec![97, 75, 47, 29, 13]
+        );
+
+        for update in &input.updates {
+            let explanation = explain(update, &input, &graph).unwrap();
+            let target = graph.order(update).unwrap().pages;
+            assert_eq!(apply_moves(update, &explanation.moves), target);
+            assert_eq!(
+                explanation.broken.is_empty(),
+                is_correct_update(update, &input.rules).is_none()
+            );
+            assert_eq!(explanation.moves.is_empty(), explanation.broken.is_empty());
+        }
+
+        // reversed, everything but one page has to move
+        let explanation = explain(&[13, 29, 47, 75, 97], &input, &graph).unwrap();
+        assert_eq!(explanation.broken.len(), 10);
+        assert_eq!(explanation.moves.len(), 4);
+
+        // the order isn't unique, but a correct update needs no moves
+        let input = parse("1|2\n\n1,2,3\n");
+        let graph = RuleGraph::new(&input.rules);
+        assert_eq!(
+            explain(&[1, 2, 3], &input, &graph),
+            Ok(Explanation {
+                broken: vec![],
+                moves: vec![]
+            })
+        );
+        let explanation = explain(&[2, 3, 1], &input, &graph).unwrap();
+        assert_eq!(
+            explanation.broken,
+            vec![BrokenRule {
+                before: 1,
+                after: 2,
+                line: 1
+            }]
+        );
+        assert_eq!(explanation.moves.len(), 1);
+        assert_eq!(apply_moves(&[2, 3, 1], &explanation.moves), vec![3, 1, 2]);
+
+        assert_eq!(longest_increasing(&[3, 1, 2, 5, 4, 6]), vec![1, 2, 4, 5]);
+        assert_eq!(longest_increasing(&[]), vec![]);
+    }
 }
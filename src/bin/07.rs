@@ -14,6 +14,12 @@ takes 5 seconds but I imagine we could speed up a lot by reusing the computation
 (iterating through a tree where you pick 1 of 3 iterations at next edge). And a key property of all 3 operations is that the result only gets larger which means that
 a lot of the tree doesn't need to be explored.
 
+Even better is to search from the right (solutions): the last number has to be un-added, un-multiplied (only if it
+divides) or un-concatenated (only if it's a suffix), which prunes almost everything, and finds the operators too.
+
+Operators are pluggable (Operator). When every operator in the set can be undone for every number (so not multiplying by
+0) the search goes right to left, otherwise it falls back to evaluating left to right. Either way the equation still means left to right evaluation.
+
 */
 
 fn main() {
@@ -26,6 +32,55 @@ fn main() {
     let part2 = part2(&input);
     println!("Part 2: {}", part2);
     assert_eq!(part2, 97902809384118);
+
+    // `--brute-force` checks against trying every combination of operators, `--show` prints the equations
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|a| a == "--brute-force") {
+        let brute_force: i64 = input
+            .iter()
+            .filter(|equation| num_solutions(equation) >= 1)
+            .map(|equation| equation.lhs)
+            .sum();
+        assert_eq!(brute_force, part1);
+        let brute_force: i64 = input
+            .iter()
+            .filter(|equation| num_solutions_part2(equation) >= 1)
+            .map(|equation| equation.lhs)
+            .sum();
+        assert_eq!(brute_force, part2);
+    }
+    // e.g. `--ops +,*,||,-,^,||2` for a different set of operators
+    let ops: Vec<Box<dyn Operator>> = match args.iter().position(|a| a == "--ops") {
+        Some(k) => args
+            .get(k + 1)
+            .expect("missing argument value")
+            .split(',')
+            .map(|symbol| operator(symbol).unwrap_or_else(|| panic!("unknown operator {}", symbol)))
+            .collect(),
+        None => vec![Box::new(Add), Box::new(Mul), Box::new(Concat { base: 10 })],
+    };
+    let ops: Vec<&dyn Operator> = ops.iter().map(|op| op.as_ref()).collect();
+    if args.iter().any(|a| a == "--ops") {
+        let total: i64 = input
+            .iter()
+            .filter(|equation| !solutions(equation, &ops, false).is_empty())
+            .map(|equation| equation.lhs)
+            .sum();
+        println!("Total calibration result: {}", total);
+    }
+    if args.iter().any(|a| a == "--show") {
+        for equation in &input {
+            let solutions = solutions(equation, &ops, true);
+            if let Some(chosen) = solutions.first() {
+                assert_eq!(evaluate(equation, &ops, chosen), Some(equation.lhs));
+                println!(
+                    "{} ({} solutions)",
+                    format_equation(equation, &ops, chosen),
+                    solutions.len()
+                );
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -76,7 +131,7 @@ fn num_solutions(equation: &Equation) -> i64 {
 fn part1(input: &Input) -> i64 {
     let mut sum = 0;
     for equation in input {
-        if num_solutions(&equation) >= 1 {
+        if !solutions(equation, &PART1_OPS, false).is_empty() {
             sum += equation.lhs;
         }
     }
@@ -124,13 +179,275 @@ fn num_solutions_part2(equation: &Equation) -> i64 {
 fn part2(input: &Input) -> i64 {
     let mut sum = 0;
     for equation in input {
-        if num_solutions_part2(&equation) >= 1 {
+        if !solutions(equation, &PART2_OPS, false).is_empty() {
             sum += equation.lhs;
         }
     }
     sum
 }
 
+// binary operator, applied left to right
+trait Operator {
+    fn symbol(&self) -> String;
+
+    // None if undefined or overflows
+    fn apply(&self, a: i64, b: i64) -> Option<i64>;
+
+    // `a` with apply(a, b) == target, only used when has_inverse(b)
+    fn undo(&self, _target: i64, _b: i64) -> Option<i64> {
+        None
+    }
+
+    // whether undo gives the only `a`, so we can search backward
+    fn has_inverse(&self, _b: i64) -> bool {
+        false
+    }
+
+    // whether apply(a, b) >= a for positive a and b, lets the search prune
+    fn grows(&self) -> bool {
+        false
+    }
+}
+
+struct Add;
+struct Mul;
+struct Sub;
+struct Xor;
+// append the digits of b in `base` to a
+struct Concat {
+    base: i64,
+}
+
+impl Operator for Add {
+    fn symbol(&self) -> String {
+        "+".to_string()
+    }
+    fn apply(&self, a: i64, b: i64) -> Option<i64> {
+        a.checked_add(b)
+    }
+    fn undo(&self, target: i64, b: i64) -> Option<i64> {
+        target.checked_sub(b)
+    }
+    fn has_inverse(&self, _b: i64) -> bool {
+        true
+    }
+    fn grows(&self) -> bool {
+        true
+    }
+}
+
+impl Operator for Mul {
+    fn symbol(&self) -> String {
+        "*".to_string()
+    }
+    fn apply(&self, a: i64, b: i64) -> Option<i64> {
+        a.checked_mul(b)
+    }
+    fn undo(&self, target: i64, b: i64) -> Option<i64> {
+        (target % b == 0).then(|| target / b)
+    }
+    // anything times 0 is 0
+    fn has_inverse(&self, b: i64) -> bool {
+        b != 0
+    }
+    fn grows(&self) -> bool {
+        true
+    }
+}
+
+impl Operator for Sub {
+    fn symbol(&self) -> String {
+        "-".to_string()
+    }
+    fn apply(&self, a: i64, b: i64) -> Option<i64> {
+        a.checked_sub(b)
+    }
+    fn undo(&self, target: i64, b: i64) -> Option<i64> {
+        target.checked_add(b)
+    }
+    fn has_inverse(&self, _b: i64) -> bool {
+        true
+    }
+}
+
+impl Operator for Xor {
+    fn symbol(&self) -> String {
+        "^".to_string()
+    }
+    fn apply(&self, a: i64, b: i64) -> Option<i64> {
+        Some(a ^ b)
+    }
+    fn undo(&self, target: i64, b: i64) -> Option<i64> {
+        Some(target ^ b)
+    }
+    fn has_inverse(&self, _b: i64) -> bool {
+        true
+    }
+}
+
+impl Concat {
+    // base^(number of digits of b)
+    fn shift(&self, b: i64) -> Option<i64> {
+        let mut shift: i64 = self.base;
+        while shift <= b {
+            shift = shift.checked_mul(self.base)?;
+        }
+        Some(shift)
+    }
+}
+
+// Only defined for non-negative numbers. Base 10 is concat_op
+impl Operator for Concat {
+    fn symbol(&self) -> String {
+        match self.base {
+            10 => "||".to_string(),
+            base => format!("||{}", base),
+        }
+    }
+    fn apply(&self, a: i64, b: i64) -> Option<i64> {
+        if a < 0 || b < 0 {
+            return None;
+        }
+        a.checked_mul(self.shift(b)?)?.checked_add(b)
+    }
+    // strip b's digits off the end of target
+    fn undo(&self, target: i64, b: i64) -> Option<i64> {
+        if target < 0 || b < 0 {
+            return None;
+        }
+        let shift = self.shift(b)?;
+        (target % shift == b).then(|| target / shift)
+    }
+    fn has_inverse(&self, _b: i64) -> bool {
+        true
+    }
+    fn grows(&self) -> bool {
+        true
+    }
+}
+
+const PART1_OPS: [&dyn Operator; 2] = [&Add, &Mul];
+const PART2_OPS: [&dyn Operator; 3] = [&Add, &Mul, &Concat { base: 10 }];
+
+// The operators that can be picked with --ops
+fn operator(symbol: &str) -> Option<Box<dyn Operator>> {
+    match symbol {
+        "+" => Some(Box::new(Add)),
+        "*" => Some(Box::new(Mul)),
+        "-" => Some(Box::new(Sub)),
+        "^" => Some(Box::new(Xor)),
+        "||" => Some(Box::new(Concat { base: 10 })),
+        _ => {
+            let base = symbol.strip_prefix("||")?.parse().ok()?;
+            (base >= 2).then(|| Box::new(Concat { base }) as Box<dyn Operator>)
+        }
+    }
+}
+
+// operators (indices into `ops`) for one or all ways to make the equation true
+// backward if every operator can be undone, otherwise forward
+fn solutions(equation: &Equation, ops: &[&dyn Operator], all: bool) -> Vec<Vec<usize>> {
+    // growing operators never make a partial result smaller than the first number, as long as all numbers are positive
+    let prune = ops.iter().all(|op| op.grows()) && equation.rhs.iter().all(|&n| n > 0);
+    let mut search = Search {
+        ops,
+        all,
+        prune,
+        chosen: vec![],
+        found: vec![],
+    };
+    let invertible = ops
+        .iter()
+        .all(|op| equation.rhs[1..].iter().all(|&b| op.has_inverse(b)));
+    if invertible {
+        search.backward(&equation.rhs, equation.lhs);
+        for found in &mut search.found {
+            found.reverse();
+        }
+    } else {
+        search.forward(&equation.rhs[1..], equation.rhs[0], equation.lhs);
+    }
+    search.found
+}
+
+struct Search<'a> {
+    ops: &'a [&'a dyn Operator],
+    all: bool,
+    prune: bool,
+    // operators picked so far. Right to left when searching backward
+    chosen: Vec<usize>,
+    found: Vec<Vec<usize>>,
+}
+
+impl Search<'_> {
+    // Undo the last number of rhs in every way that works. Returns true to stop searching
+    fn backward(&mut self, rhs: &[i64], target: i64) -> bool {
+        let (&last, rest) = rhs.split_last().unwrap();
+        if rest.is_empty() {
+            return target == last && self.found();
+        }
+        for (k, op) in self.ops.iter().enumerate() {
+            let Some(prev) = op.undo(target, last) else {
+                continue;
+            };
+            if self.prune && prev < rest[0] {
+                continue;
+            }
+            self.chosen.push(k);
+            let stop = self.backward(rest, prev);
+            self.chosen.pop();
+            if stop {
+                return true;
+            }
+        }
+        false
+    }
+
+    // Apply every operator to the next number of rhs. Returns true to stop searching
+    fn forward(&mut self, rhs: &[i64], value: i64, target: i64) -> bool {
+        let Some((&next, rest)) = rhs.split_first() else {
+            return value == target && self.found();
+        };
+        for (k, op) in self.ops.iter().enumerate() {
+            let Some(value) = op.apply(value, next) else {
+                continue;
+            };
+            if self.prune && value > target {
+                continue;
+            }
+            self.chosen.push(k);
+            let stop = self.forward(rest, value, target);
+            self.chosen.pop();
+            if stop {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn found(&mut self) -> bool {
+        self.found.push(self.chosen.clone());
+        !self.all
+    }
+}
+
+fn format_equation(equation: &Equation, ops: &[&dyn Operator], chosen: &[usize]) -> String {
+    let mut out = format!("{}: {}", equation.lhs, equation.rhs[0]);
+    for (&k, n) in chosen.iter().zip(&equation.rhs[1..]) {
+        out.push_str(&format!(" {} {}", ops[k].symbol(), n));
+    }
+    out
+}
+
+// Evaluate left to right, as the puzzle does
+fn evaluate(equation: &Equation, ops: &[&dyn Operator], chosen: &[usize]) -> Option<i64> {
+    let mut value = equation.rhs[0];
+    for (&k, &n) in chosen.iter().zip(&equation.rhs[1..]) {
+        value = ops[k].apply(value, n)?;
+    }
+    Some(value)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,4 +458,145 @@ mod tests {
         assert_eq!(part1(&input), 3749);
         assert_eq!(part2(&input), 11387);
     }
+
+    #[test]
+    fn test_solutions() {
+        let input = parse(include_str!("../../input/07_example.txt"));
+        let shown: Vec<String> = input
+            .iter()
+            .flat_map(|equation| {
+                solutions(equation, &PART2_OPS, true)
+                    .into_iter()
+                    .map(|chosen| format_equation(equation, &PART2_OPS, &chosen))
+            })
+            .collect();
+        assert_eq!(
+            shown,
+            vec![
+                "190: 10 * 19",
+                "3267: 81 * 40 + 27",
+                "3267: 81 + 40 * 27",
+                "156: 15 || 6",
+                "7290: 6 * 8 || 6 * 15",
+                "192: 17 || 8 + 14",
+                "292: 11 + 6 * 16 + 20",
+            ]
+        );
+        assert_eq!(solutions(&input[8], &PART1_OPS, true).len(), 1);
+        assert_eq!(solutions(&input[3], &PART1_OPS, true).len(), 0);
+
+        // counts agree with trying every combination
+        for equation in &input {
+            assert_eq!(
+                solutions(equation, &PART2_OPS, true).len() as i64,
+                num_solutions_part2(equation)
+            );
+        }
+
+        // only one way to make 1, but three to make 2
+        let equation = Equation {
+            lhs: 1,
+            rhs: vec![1, 1, 1, 1],
+        };
+        assert_eq!(solutions(&equation, &PART2_OPS, true), vec![vec![1; 3]]);
+        let equation = Equation {
+            lhs: 2,
+            rhs: vec![1, 1, 1, 1],
+        };
+        assert_eq!(
+            solutions(&equation, &PART2_OPS, true),
+            vec![vec![1, 1, 0], vec![1, 0, 1], vec![0, 1, 1]]
+        );
+        assert_eq!(solutions(&equation, &PART2_OPS, false), vec![vec![1, 1, 0]]);
+        let equation = Equation {
+            lhs: 4,
+            rhs: vec![2, 2],
+        };
+        assert_eq!(
+            solutions(&equation, &PART2_OPS, true),
+            vec![vec![0], vec![1]]
+        );
+
+        let concat = Concat { base: 10 };
+        assert_eq!(concat.undo(12345, 345), Some(12));
+        assert_eq!(concat.undo(12345, 45), Some(123));
+        assert_eq!(concat.undo(12345, 44), None);
+        assert_eq!(concat.undo(7, 7), Some(0));
+        for (a, b) in [(1, 2), (15, 6), (48, 6), (999, 1000), (0, 7)] {
+            assert_eq!(concat.apply(a, b), concat_op(a, b));
+        }
+    }
+
+    // min isn't invertible, so equations using it are searched left to right
+    struct Min;
+
+    impl Operator for Min {
+        fn symbol(&self) -> String {
+            "min".to_string()
+        }
+        fn apply(&self, a: i64, b: i64) -> Option<i64> {
+            Some(a.min(b))
+        }
+    }
+
+    #[test]
+    fn test_operators() {
+        let equation = |lhs: i64, rhs: &[i64]| Equation {
+            lhs,
+            rhs: rhs.to_vec(),
+        };
+        let ops: Vec<Box<dyn Operator>> = ["+", "-", "^", "||2"]
+            .iter()
+            .map(|symbol| operator(symbol).unwrap())
+            .collect();
+        let ops: Vec<&dyn Operator> = ops.iter().map(|op| op.as_ref()).collect();
+        assert!(operator("||1").is_none() && operator("/").is_none());
+
+        // left to right: (5 - 7) + 3 = 1, and (5 ^ 7) ^ 3 = 1
+        let e = equation(1, &[5, 7, 3]);
+        assert_eq!(solutions(&e, &ops, true), vec![vec![1, 0], vec![2, 2]]);
+        assert_eq!(format_equation(&e, &ops, &[1, 0]), "1: 5 - 7 + 3");
+        // 0b101 || 0b11 = 0b10111, then ^ 1
+        let e = equation(0b10110, &[5, 3, 1]);
+        assert!(solutions(&e, &ops, true).contains(&vec![3, 2]));
+        assert_eq!(Concat { base: 2 }.apply(5, 3), Some(0b10111));
+        assert_eq!(Concat { base: 2 }.undo(0b10111, 3), Some(5));
+        assert_eq!(Concat { base: 16 }.apply(0xab, 0xcd), Some(0xabcd));
+
+        // times 0 can't be undone, so these are searched forward
+        let e = equation(0, &[5, 0]);
+        assert_eq!(solutions(&e, &PART2_OPS, true), vec![vec![1]]);
+        let e = equation(6, &[3, 0, 6]);
+        let found = solutions(&e, &PART2_OPS, true);
+        assert_eq!(found, vec![vec![1, 0], vec![1, 2]]);
+        assert_eq!(found.len() as i64, num_solutions_part2(&e));
+
+        // every solution found, backward or forward, evaluates to the left hand side
+        let with_min = [&Add as &dyn Operator, &Sub, &Min];
+        for e in [
+            equation(3, &[2, 4, 1, 3]),
+            equation(10, &[4, 3, 2, 1, 6]),
+            equation(-6, &[1, 2, 3, 4, 5]),
+        ] {
+            for ops in [&ops[..], &with_min[..], &PART2_OPS[..]] {
+                let found = solutions(&e, ops, true);
+                let mut count = 0;
+                // all combinations of operators
+                for i in 0..ops.len().pow(e.rhs.len() as u32 - 1) {
+                    let chosen: Vec<usize> = (0..e.rhs.len() - 1)
+                        .map(|j| i / ops.len().pow(j as u32) % ops.len())
+                        .collect();
+                    if evaluate(&e, ops, &chosen) == Some(e.lhs) {
+                        count += 1;
+                        assert!(found.contains(&chosen));
+                    }
+                }
+                assert_eq!(found.len(), count);
+            }
+        }
+        assert_eq!(
+            solutions(&equation(5, &[4, 1, 9]), &with_min, false),
+            vec![vec![0, 2]]
+        );
+    }
 }
@@ -10,9 +10,12 @@ The brute force way doesn't work but we can reduce the problem to a system of tw
 Then if det!=0 there is a unique solution, (which we check is integer), or if det=0 there is no solution or there are infinite solutions
 This case didn't happen in the input.
 
-If this were to occur, we then we'd quickly check if one equation was a (rational) multiple of the other to know if we have infinite solutions
+For det=0 (ClawMachine::solve_collinear) we check the prize lies on the same line as the buttons, then drop one of the dimensions
+leaving a single equation a*u + b*v = p. Extended euclid gives every integer solution as a line in t, the token cost is linear
+in t, so the cheapest non-negative solution is at one end of the range of t where a, b >= 0.
 
-We then drop one of the dimensions but this case is kinda annoying because we have to pick out integer solutions from the infinite solutions
+Both parts now go through ClawMachine::solve, which also takes the 100 press cap of part 1 as extra bounds on t.
+The brute force is kept as ClawMachine::brute_force to check against.
 
 
 */
@@ -27,6 +30,13 @@ fn main() {
     let part2 = part2(&input);
     println!("Part 2: {}", part2);
     assert_eq!(part2, 108528956728655);
+
+    let brute_force: i128 = input
+        .iter()
+        .filter_map(|machine| ClawMachine::part1().brute_force(machine))
+        .map(|presses| presses.cost)
+        .sum();
+    assert_eq!(brute_force, part1 as i128);
 }
 
 #[derive(Debug)]
@@ -76,59 +86,229 @@ fn parse(input: &str) -> Input {
 }
 
 fn part1(input: &Input) -> i64 {
-    let mut sum = 0;
-    for machine in input {
-        let mut min_token_cost = None;
-        for a_presses in 0..=100 {
-            for b_presses in 0..=100 {
-                let x = machine.button_a.0 * a_presses + machine.button_b.0 * b_presses;
-                let y = machine.button_a.1 * a_presses + machine.button_b.1 * b_presses;
-                if x == machine.prize.0 && y == machine.prize.1 {
-                    let token_cost = a_presses * 3 + b_presses;
-                    match min_token_cost {
-                        None => min_token_cost = Some(token_cost),
-                        Some(min) => min_token_cost = Some(min.min(token_cost)),
-                    }
-                }
-            }
+    ClawMachine::part1().total_cost(input).try_into().unwrap()
+}
+
+fn part2(input: &Input) -> i64 {
+    ClawMachine::part2().total_cost(input).try_into().unwrap()
+}
+
+// tokens per press of each button
+#[derive(Debug, Clone, Copy)]
+struct Costs {
+    a: i64,
+    b: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Presses {
+    a: i128,
+    b: i128,
+    cost: i128,
+}
+
+// button costs, how far the prizes are moved, and an optional cap on presses
+#[derive(Debug, Clone, Copy)]
+struct ClawMachine {
+    costs: Costs,
+    offset: i64,
+    max_presses: Option<i64>,
+}
+
+impl ClawMachine {
+    fn part1() -> ClawMachine {
+        ClawMachine {
+            costs: Costs { a: 3, b: 1 },
+            offset: 0,
+            max_presses: Some(100),
         }
-        if let Some(min_token_cost) = min_token_cost {
-            sum += min_token_cost;
+    }
+
+    fn part2() -> ClawMachine {
+        ClawMachine {
+            costs: Costs { a: 3, b: 1 },
+            offset: 10000000000000,
+            max_presses: None,
         }
     }
-    sum
-}
 
-fn part2(input: &Input) -> i64 {
-    let mut sum = 0;
-    for machine in input {
-        let prize_x = machine.prize.0 + 10000000000000;
-        let prize_y = machine.prize.1 + 10000000000000;
-        println!(
-            "{}a + {}b = {}",
-            machine.button_a.0, machine.button_b.0, prize_x
-        );
-        println!(
-            "{}a + {}b = {}",
-            machine.button_a.1, machine.button_b.1, prize_y
-        );
+    fn prize(&self, machine: &Machine) -> (i128, i128) {
+        (
+            machine.prize.0 as i128 + self.offset as i128,
+            machine.prize.1 as i128 + self.offset as i128,
+        )
+    }
+
+    fn presses(&self, a: i128, b: i128) -> Presses {
+        let cost = a * self.costs.a as i128 + b * self.costs.b as i128;
+        Presses { a, b, cost }
+    }
 
-        let det = machine.button_a.0 * machine.button_b.1 - machine.button_a.1 * machine.button_b.0;
+    fn within_cap(&self, presses: i128) -> bool {
+        presses >= 0 && self.max_presses.is_none_or(|max| presses <= max as i128)
+    }
+
+    fn total_cost(&self, input: &Input) -> i128 {
+        input
+            .iter()
+            .filter_map(|machine| self.solve(machine))
+            .map(|presses| presses.cost)
+            .sum()
+    }
+
+    // cheapest presses reaching the prize, if any
+    fn solve(&self, machine: &Machine) -> Option<Presses> {
+        assert!(self.costs.a >= 0 && self.costs.b >= 0);
+        let (ax, ay) = (machine.button_a.0 as i128, machine.button_a.1 as i128);
+        let (bx, by) = (machine.button_b.0 as i128, machine.button_b.1 as i128);
+        let (px, py) = self.prize(machine);
+
+        let det = ax * by - ay * bx;
         if det == 0 {
-            panic!("det = 0");
+            return self.solve_collinear((ax, ay), (bx, by), (px, py));
         }
-        let a_det = prize_x * machine.button_b.1 - prize_y * machine.button_b.0;
-        let b_det = machine.button_a.0 * prize_y - machine.button_a.1 * prize_x;
+        let a_det = px * by - py * bx;
+        let b_det = ax * py - ay * px;
         if a_det % det != 0 || b_det % det != 0 {
-            println!("No integer solution");
+            return None;
+        }
+        let (a, b) = (a_det / det, b_det / det);
+        if !self.within_cap(a) || !self.within_cap(b) {
+            return None;
+        }
+        Some(self.presses(a, b))
+    }
+
+    fn solve_collinear(
+        &self,
+        (ax, ay): (i128, i128),
+        (bx, by): (i128, i128),
+        (px, py): (i128, i128),
+    ) -> Option<Presses> {
+        // the prize has to be on the line both buttons move along
+        if ax * py - ay * px != 0 || bx * py - by * px != 0 {
+            return None;
+        }
+        // and then one dimension is enough, unless it's zero for both buttons
+        let (u, v, p) = if ax != 0 || bx != 0 {
+            (ax, bx, px)
+        } else if ay != 0 || by != 0 {
+            (ay, by, py)
         } else {
-            let a = a_det / det;
-            let b = b_det / det;
-            println!("a = {}, b = {}", a, b);
-            sum += a * 3 + b;
+            // neither button moves the claw
+            return (px == 0 && py == 0).then(|| self.presses(0, 0));
+        };
+
+        if u == 0 {
+            let b = p / v;
+            return (p % v == 0 && self.within_cap(b)).then(|| self.presses(0, b));
+        }
+        if v == 0 {
+            let a = p / u;
+            return (p % u == 0 && self.within_cap(a)).then(|| self.presses(a, 0));
+        }
+
+        // a*u + b*v = p has solutions a = a0 + s*t, b = b0 - r*t
+        let (g, x, y) = extended_gcd(u, v);
+        if p % g != 0 {
+            return None;
+        }
+        let (a0, b0) = (x * (p / g), y * (p / g));
+        let (s, r) = (v / g, u / g);
+
+        // range of t where a and b are within 0..=max_presses, None for unbounded
+        let mut lo: Option<i128> = None;
+        let mut hi: Option<i128> = None;
+        let mut bound = |t: i128, lower: bool| {
+            if lower {
+                lo = Some(lo.map_or(t, |lo| lo.max(t)));
+            } else {
+                hi = Some(hi.map_or(t, |hi| hi.min(t)));
+            }
+        };
+        // a0 + s*t >= 0 and b0 - r*t >= 0
+        if s > 0 {
+            bound(div_ceil(-a0, s), true);
+        } else {
+            bound(div_floor(-a0, s), false);
+        }
+        if r > 0 {
+            bound(div_floor(b0, r), false);
+        } else {
+            bound(div_ceil(b0, r), true);
+        }
+        // a0 + s*t <= max and b0 - r*t <= max
+        if let Some(max) = self.max_presses {
+            let max = max as i128;
+            if s > 0 {
+                bound(div_floor(max - a0, s), false);
+            } else {
+                bound(div_ceil(max - a0, s), true);
+            }
+            if r > 0 {
+                bound(div_ceil(b0 - max, r), true);
+            } else {
+                bound(div_floor(b0 - max, r), false);
+            }
+        }
+        if let (Some(lo), Some(hi)) = (lo, hi) {
+            if lo > hi {
+                return None;
+            }
+        }
+
+        // the cost changes by a linear amount per step of t. It can't decrease forever because a, b >= 0
+        let slope = self.costs.a as i128 * s - self.costs.b as i128 * r;
+        let t = match slope {
+            slope if slope > 0 => lo.unwrap(),
+            slope if slope < 0 => hi.unwrap(),
+            _ => lo.or(hi).unwrap(),
+        };
+        Some(self.presses(a0 + s * t, b0 - r * t))
+    }
+
+    // try every combination up to the cap, ties go to fewest A presses
+    fn brute_force(&self, machine: &Machine) -> Option<Presses> {
+        let max = self.max_presses.expect("brute force needs a press cap") as i128;
+        let (px, py) = self.prize(machine);
+        let mut best: Option<Presses> = None;
+        for a in 0..=max {
+            for b in 0..=max {
+                let x = machine.button_a.0 as i128 * a + machine.button_b.0 as i128 * b;
+                let y = machine.button_a.1 as i128 * a + machine.button_b.1 as i128 * b;
+                if (x, y) == (px, py) {
+                    let presses = self.presses(a, b);
+                    if best.is_none_or(|best| presses.cost < best.cost) {
+                        best = Some(presses);
+                    }
+                }
+            }
         }
+        best
     }
-    sum
+}
+
+// (g, x, y) with a*x + b*y = g
+fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - (a / b) * y)
+    }
+}
+
+fn div_floor(a: i128, b: i128) -> i128 {
+    let q = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn div_ceil(a: i128, b: i128) -> i128 {
+    -div_floor(-a, b)
 }
 
 #[cfg(test)]
@@ -141,4 +321,120 @@ mod tests {
         assert_eq!(part1(&input), 480);
         assert_eq!(part2(&input), 875318608908);
     }
+
+    fn machine(button_a: (i64, i64), button_b: (i64, i64), prize: (i64, i64)) -> Machine {
+        Machine {
+            button_a,
+            button_b,
+            prize,
+        }
+    }
+
+    fn min_presses(machine: &Machine) -> Option<(i128, i128)> {
+        ClawMachine::part1()
+            .solve(machine)
+            .map(|presses| (presses.a, presses.b))
+    }
+
+    #[test]
+    fn test_collinear() {
+        // B is cheaper per unit of distance so only B is pressed
+        assert_eq!(
+            min_presses(&machine((2, 4), (1, 2), (10, 20))),
+            Some((0, 10))
+        );
+        // A moves more than 3 times as far as B so it's worth pressing as much as possible
+        assert_eq!(min_presses(&machine((8, 4), (2, 1), (14, 7))), Some((1, 3)));
+        assert_eq!(
+            min_presses(&machine((4, 6), (6, 9), (14, 21))),
+            Some((2, 1))
+        );
+        // off the line
+        assert_eq!(min_presses(&machine((2, 4), (1, 2), (10, 21))), None);
+        // on the line but only reachable with negative presses, or not at all
+        assert_eq!(min_presses(&machine((4, 6), (6, 9), (2, 3))), None);
+        assert_eq!(min_presses(&machine((4, 6), (6, 9), (1, 1))), None);
+        assert_eq!(min_presses(&machine((4, 6), (6, 9), (-10, -15))), None);
+        // buttons that don't move in one or both dimensions
+        assert_eq!(min_presses(&machine((1, 1), (0, 0), (5, 5))), Some((5, 0)));
+        assert_eq!(min_presses(&machine((0, 4), (0, 1), (0, 9))), Some((2, 1)));
+        assert_eq!(min_presses(&machine((0, 0), (0, 0), (0, 0))), Some((0, 0)));
+        assert_eq!(min_presses(&machine((0, 0), (0, 0), (1, 0))), None);
+        // the press cap rules out pressing only B
+        assert_eq!(
+            min_presses(&machine((2, 4), (1, 2), (300, 600))),
+            Some((100, 100))
+        );
+        assert_eq!(min_presses(&machine((2, 4), (1, 2), (301, 602))), None);
+
+        // with the part 2 offset
+        let presses = ClawMachine::part2()
+            .solve(&machine((97, 97), (13, 13), (0, 0)))
+            .unwrap();
+        assert!(presses.a >= 0 && presses.b >= 0 && presses.b < 97);
+        assert_eq!(97 * presses.a + 13 * presses.b, 10000000000000);
+        assert_eq!(presses.cost, 3 * presses.a + presses.b);
+
+        // offsets near the i64 limit, with a cost that doesn't fit in an i64
+        let far = ClawMachine {
+            offset: i64::MAX - 5,
+            ..ClawMachine::part2()
+        };
+        let machine = machine((1, 0), (0, 1), (10, 20));
+        let presses = far.solve(&machine).unwrap();
+        assert_eq!(presses.a, i64::MAX as i128 + 5);
+        assert_eq!(presses.b, i64::MAX as i128 + 15);
+        assert_eq!(far.total_cost(&vec![machine]), 4 * i64::MAX as i128 + 30);
+    }
+
+    #[test]
+    fn test_claw_machine() {
+        let input = parse(include_str!("../../input/13_example.txt"));
+        let presses: Vec<_> = input
+            .iter()
+            .map(|machine| ClawMachine::part1().solve(machine))
+            .collect();
+        assert_eq!(
+            presses[0],
+            Some(Presses {
+                a: 80,
+                b: 40,
+                cost: 280
+            })
+        );
+        assert_eq!(presses[1], None);
+        assert_eq!(presses[2].map(|p| (p.a, p.b)), Some((38, 86)));
+        assert_eq!(presses[3], None);
+
+        // agrees with brute force on small machines, collinear or not, for a few cost models and caps
+        let buttons = [(2, 4), (8, 4), (3, 0), (0, 5), (6, 9), (1, 3)];
+        for costs in [(3, 1), (1, 1), (1, 5), (0, 2)] {
+            for max_presses in [7, 20] {
+                let claw = ClawMachine {
+                    costs: Costs {
+                        a: costs.0,
+                        b: costs.1,
+                    },
+                    offset: 0,
+                    max_presses: Some(max_presses),
+                };
+                for &button_a in &buttons {
+                    for &button_b in &buttons {
+                        for px in 0..40 {
+                            for py in [0, px / 2, px, 2 * px, 3 * px] {
+                                let machine = machine(button_a, button_b, (px, py));
+                                assert_eq!(
+                                    claw.solve(&machine).map(|p| p.cost),
+                                    claw.brute_force(&machine).map(|p| p.cost),
+                                    "{:?} {:?}",
+                                    claw,
+                                    machine
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
 }
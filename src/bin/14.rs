@@ -11,18 +11,60 @@ hint is from part 1 where we are computing a basic 'entropy' of the picture. We
 
 the x coords are periodic every 101 values and the y coords are periodic every 103 values. So the max period is 101*103 = 10303
 
+Which also means the x and y alignments can be found independently (101 + 103 timesteps) and combined with CRT, see
+part2_crt.
+
 */
 
 fn main() {
-    let input = parse(include_str!("../../input/14.txt"));
+    let raw_input = include_str!("../../input/14.txt");
+    let input = parse(raw_input);
+
+    // e.g. `--size 101x103`, or `--detector variance --out tree.png` to compare heuristics
+    let args: Vec<String> = std::env::args().collect();
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .map(|k| args.get(k + 1).expect("missing argument value").clone())
+    };
 
-    let part1 = part1(&input, 101, 103);
+    // CLI flag, then header line, then inferred from the robot positions
+    let arena = match arg_value("--size") {
+        Some(size) => parse_size(&size).unwrap(),
+        None => parse_arena(raw_input)
+            .unwrap()
+            .unwrap_or_else(|| infer_arena(&input)),
+    };
+    validate_arena(&input, &arena).unwrap();
+    let (width, height) = (arena.width, arena.height);
+
+    let part1 = part1(&input, width, height);
     println!("Part 1: {}", part1);
-    assert_eq!(part1, 232589280);
 
-    let part2 = part2(&input, 101, 103);
+    let part2 = part2(&input, width, height);
     println!("Part 2: {}", part2);
-    assert_eq!(part2, 7569);
+
+    // part2_crt uses a different heuristic, so on other arenas it doesn't have to agree
+    if (width, height) == (101, 103) {
+        assert_eq!(part1, 232589280);
+        assert_eq!(part2, 7569);
+        assert_eq!(part2_crt(&input, width, height), part2);
+    }
+
+    if let Some(detector) = arg_value("--detector") {
+        let detector: Detector = detector.parse().unwrap();
+        let t = find_picture(&input, width, height, detector);
+        println!("{:?}: t = {}", detector, t);
+        let grid = grid_at(&input, width, height, t);
+        print_grid(&grid);
+        if let Some(path) = arg_value("--out") {
+            if path.ends_with(".png") {
+                write_png(&grid, &path, 4).unwrap();
+            } else {
+                std::fs::write(&path, to_pbm(&grid)).unwrap();
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -38,7 +80,7 @@ type Input = Vec<Robot>;
 fn parse(input: &str) -> Input {
     input
         .lines()
-        .filter(|l| !l.is_empty())
+        .filter(|l| !l.is_empty() && !l.starts_with("size="))
         .map(|line| {
             // p=0,4 v=3,-3
             let re = regex::Regex::new(r"^p=(-?\d+),(-?\d+) v=(-?\d+),(-?\d+)$").unwrap();
@@ -53,32 +95,123 @@ fn parse(input: &str) -> Input {
         .collect()
 }
 
+#[derive(Debug, PartialEq)]
+struct Arena {
+    width: i64,
+    height: i64,
+}
+
+// "101x103"
+fn parse_size(size: &str) -> Result<Arena, String> {
+    let (width, height) = size
+        .split_once('x')
+        .ok_or_else(|| format!("invalid size: {}", size))?;
+    let width = width
+        .parse()
+        .map_err(|_| format!("invalid width: {}", width))?;
+    let height = height
+        .parse()
+        .map_err(|_| format!("invalid height: {}", height))?;
+    Ok(Arena { width, height })
+}
+
+// Optional `size=101x103` header line
+fn parse_arena(input: &str) -> Result<Option<Arena>, String> {
+    let Some(size) = input.lines().next().and_then(|l| l.strip_prefix("size=")) else {
+        return Ok(None);
+    };
+    parse_size(size).map(Some)
+}
+
+// Smallest arena containing every starting position. Fine for the real input and the example, since
+// both have robots starting on the far edges
+fn infer_arena(input: &Input) -> Arena {
+    Arena {
+        width: input.iter().map(|r| r.x).max().unwrap() + 1,
+        height: input.iter().map(|r| r.y).max().unwrap() + 1,
+    }
+}
+
+fn validate_arena(input: &Input, arena: &Arena) -> Result<(), String> {
+    if arena.width <= 0 || arena.height <= 0 {
+        return Err(format!("invalid arena {}x{}", arena.width, arena.height));
+    }
+    for (k, robot) in input.iter().enumerate() {
+        if !(0..arena.width).contains(&robot.x) || !(0..arena.height).contains(&robot.y) {
+            return Err(format!(
+                "robot {} starts at ({}, {}), outside the {}x{} arena",
+                k, robot.x, robot.y, arena.width, arena.height
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn part1(input: &Input, width: i64, height: i64) -> i64 {
+    let grid = grid_at(input, width, height, 100);
+    print_grid(&grid);
+    quadrant_product(&grid)
+}
+
+fn part2(input: &Input, width: i64, height: i64) -> i64 {
+    find_picture(input, width, height, Detector::QuadrantProduct)
+}
+
+// Robot counts per cell at time t, indexed [y][x]
+fn grid_at(input: &Input, width: i64, height: i64, t: i64) -> Vec<Vec<i64>> {
     let mut grid = vec![vec![0; width as usize]; height as usize];
-    let t = 100;
-    // for each robot, we can calculate the position at time t
     for robot in input {
         let x = (robot.x + robot.vx * t).rem_euclid(width);
         let y = (robot.y + robot.vy * t).rem_euclid(height);
         grid[y as usize][x as usize] += 1;
     }
+    grid
+}
 
-    // print grid
-    for row in &grid {
-        for cell in row {
-            if *cell > 0 {
-                print!("{}", cell);
-            } else {
-                print!(".");
-            }
+// heuristics for spotting the picture, lower score is more picture-like
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Detector {
+    // part 1 safety factor
+    QuadrantProduct,
+    // variance of x plus variance of y
+    Variance,
+    // largest 4-connected group of robots, negated
+    LargestComponent,
+    // number of runs in the row by row occupancy grid
+    RunLength,
+}
+
+impl std::str::FromStr for Detector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "quadrant" => Ok(Detector::QuadrantProduct),
+            "variance" => Ok(Detector::Variance),
+            "component" => Ok(Detector::LargestComponent),
+            "rle" => Ok(Detector::RunLength),
+            _ => Err(format!("unknown detector: {}", s)),
         }
-        println!();
     }
+}
 
-    // count robots in each quadrant
-    let mut quadrants = vec![0; 4];
-    for y in 0..height {
-        for x in 0..width {
+impl Detector {
+    fn score(&self, grid: &[Vec<i64>]) -> f64 {
+        match self {
+            Detector::QuadrantProduct => quadrant_product(grid) as f64,
+            Detector::Variance => variance(grid),
+            Detector::LargestComponent => -(largest_component(grid) as f64),
+            Detector::RunLength => run_length_size(grid) as f64,
+        }
+    }
+}
+
+fn quadrant_product(grid: &[Vec<i64>]) -> i64 {
+    let height = grid.len();
+    let width = grid[0].len();
+    let mut quadrants = [0; 4];
+    for (y, row) in grid.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
             let y_cmp = (2 * y + 1).cmp(&height);
             let x_cmp = (2 * x + 1).cmp(&width);
             let quadrant = match (x_cmp, y_cmp) {
@@ -91,72 +224,212 @@ fn part1(input: &Input, width: i64, height: i64) -> i64 {
                     continue;
                 }
             };
-            quadrants[quadrant] += grid[y as usize][x as usize];
+            quadrants[quadrant] += cell;
         }
     }
+    quadrants.iter().product()
+}
 
-    let mut product = 1;
-    for quadrant in quadrants {
-        product *= quadrant;
+fn variance(grid: &[Vec<i64>]) -> f64 {
+    let (mut n, mut sx, mut sy, mut sxx, mut syy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for (y, row) in grid.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            let (c, x, y) = (*cell as f64, x as f64, y as f64);
+            n += c;
+            sx += c * x;
+            sy += c * y;
+            sxx += c * x * x;
+            syy += c * y * y;
+        }
     }
-    product
+    if n == 0.0 {
+        return 0.0;
+    }
+    (sxx / n - (sx / n).powi(2)) + (syy / n - (sy / n).powi(2))
 }
 
-fn part2(input: &Input, width: i64, height: i64) -> i64 {
-    let mut least_entropy = std::i64::MAX;
-    let mut least_entropy_t = 0;
-    for t in 0..=height * width {
-        let mut grid = vec![vec![0; width as usize]; height as usize];
-        // for each robot, we can calculate the position at time t
-        for robot in input {
-            let x = (robot.x + robot.vx * t).rem_euclid(width);
-            let y = (robot.y + robot.vy * t).rem_euclid(height);
-            grid[y as usize][x as usize] += 1;
-        }
-
-        // count robots in each quadrant
-        let mut quadrants = vec![0; 4];
-        for y in 0..height {
-            for x in 0..width {
-                let y_cmp = (2 * y + 1).cmp(&height);
-                let x_cmp = (2 * x + 1).cmp(&width);
-                let quadrant = match (x_cmp, y_cmp) {
-                    (std::cmp::Ordering::Less, std::cmp::Ordering::Less) => 0,
-                    (std::cmp::Ordering::Greater, std::cmp::Ordering::Less) => 1,
-                    (std::cmp::Ordering::Less, std::cmp::Ordering::Greater) => 2,
-                    (std::cmp::Ordering::Greater, std::cmp::Ordering::Greater) => 3,
-                    _ => {
-                        // skip center
-                        continue;
-                    }
-                };
-                quadrants[quadrant] += grid[y as usize][x as usize];
+fn largest_component(grid: &[Vec<i64>]) -> usize {
+    let height = grid.len();
+    let width = grid[0].len();
+    let mut seen = vec![vec![false; width]; height];
+    let mut largest = 0;
+    for y0 in 0..height {
+        for x0 in 0..width {
+            if grid[y0][x0] == 0 || seen[y0][x0] {
+                continue;
             }
-        }
-        let mut entropy = 1;
-        for quadrant in quadrants {
-            entropy *= quadrant;
-        }
-        if entropy < least_entropy {
-            println!("t = {}, entropy = {}", t, entropy);
-            least_entropy = entropy;
-            least_entropy_t = t;
-
-            // print grid
-            for row in &grid {
-                for cell in row {
-                    if *cell > 0 {
-                        print!("{}", cell);
-                    } else {
-                        print!(".");
+            // flood fill
+            seen[y0][x0] = true;
+            let mut stack = vec![(y0, x0)];
+            let mut size = 0;
+            while let Some((y, x)) = stack.pop() {
+                size += 1;
+                let neighbours = [
+                    (y.wrapping_sub(1), x),
+                    (y + 1, x),
+                    (y, x.wrapping_sub(1)),
+                    (y, x + 1),
+                ];
+                for (y1, x1) in neighbours {
+                    if y1 < height && x1 < width && grid[y1][x1] > 0 && !seen[y1][x1] {
+                        seen[y1][x1] = true;
+                        stack.push((y1, x1));
                     }
                 }
-                println!();
             }
+            largest = largest.max(size);
         }
     }
+    largest
+}
 
-    least_entropy_t
+fn run_length_size(grid: &[Vec<i64>]) -> usize {
+    let mut runs = 0;
+    for row in grid {
+        runs += 1;
+        for x in 1..row.len() {
+            if (row[x] > 0) != (row[x - 1] > 0) {
+                runs += 1;
+            }
+        }
+    }
+    runs
+}
+
+// Search every t in a full period for the lowest score. Ties keep the earliest t
+fn find_picture(input: &Input, width: i64, height: i64, detector: Detector) -> i64 {
+    let mut best_score = f64::MAX;
+    let mut best_t = 0;
+    for t in 0..=height * width {
+        let grid = grid_at(input, width, height, t);
+        let score = detector.score(&grid);
+        if score < best_score {
+            best_score = score;
+            best_t = t;
+        }
+    }
+    best_t
+}
+
+fn axis_variance(values: &[i64]) -> f64 {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<i64>() as f64 / n;
+    values
+        .iter()
+        .map(|v| (*v as f64 - mean).powi(2))
+        .sum::<f64>()
+        / n
+}
+
+// Time in 0..period minimising the variance of a single axis
+fn best_axis_time(input: &Input, period: i64, axis: impl Fn(&Robot, i64) -> i64) -> i64 {
+    let mut best_score = f64::MAX;
+    let mut best_t = 0;
+    for t in 0..period {
+        let values: Vec<i64> = input.iter().map(|robot| axis(robot, t)).collect();
+        let score = axis_variance(&values);
+        if score < best_score {
+            best_score = score;
+            best_t = t;
+        }
+    }
+    best_t
+}
+
+// (g, x, y) with a*x + b*y = g
+fn extended_gcd(a: i64, b: i64) -> (i64, i64, i64) {
+    if b == 0 {
+        (a, 1, 0)
+    } else {
+        let (g, x, y) = extended_gcd(b, a % b);
+        (g, y, x - (a / b) * y)
+    }
+}
+
+// solve t = a1 (mod m1), t = a2 (mod m2), moduli need not be coprime
+// returns (t, lcm) with 0 <= t < lcm, None if inconsistent
+fn crt(a1: i64, m1: i64, a2: i64, m2: i64) -> Option<(i64, i64)> {
+    let (g, p, _) = extended_gcd(m1, m2);
+    if (a2 - a1).rem_euclid(g) != 0 {
+        return None;
+    }
+    let lcm = m1 / g * m2;
+    // t = a1 + m1 * k where m1 * k = a2 - a1 (mod m2)
+    let k = ((a2 - a1) / g) as i128 * p as i128 % (m2 / g) as i128;
+    let t = (a1 as i128 + m1 as i128 * k).rem_euclid(lcm as i128);
+    Some((t as i64, lcm))
+}
+
+// x only depends on t mod width and y on t mod height, so find the best time for each axis separately
+// and combine them. That's width + height grids instead of width * height
+fn part2_crt(input: &Input, width: i64, height: i64) -> i64 {
+    let tx = best_axis_time(input, width, |r, t| (r.x + r.vx * t).rem_euclid(width));
+    let ty = best_axis_time(input, height, |r, t| (r.y + r.vy * t).rem_euclid(height));
+    if let Some((t, _)) = crt(tx, width, ty, height) {
+        return t;
+    }
+    // With a common factor the two alignments may never coincide. Fall back to the best y alignment
+    // among the times that keep the best x alignment
+    let (g, _, _) = extended_gcd(width, height);
+    let mut best_score = f64::MAX;
+    let mut best_t = tx;
+    for t in (tx..width / g * height).step_by(width as usize) {
+        let values: Vec<i64> = input
+            .iter()
+            .map(|r| (r.y + r.vy * t).rem_euclid(height))
+            .collect();
+        let score = axis_variance(&values);
+        if score < best_score {
+            best_score = score;
+            best_t = t;
+        }
+    }
+    best_t
+}
+
+// Plain (ASCII) PBM, 1 = robot
+fn to_pbm(grid: &[Vec<i64>]) -> String {
+    let mut out = format!("P1\n{} {}\n", grid[0].len(), grid.len());
+    for row in grid {
+        let line: Vec<&str> = row.iter().map(|c| if *c > 0 { "1" } else { "0" }).collect();
+        out.push_str(&line.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+// Greyscale PNG, black robots on white, each cell drawn as a scale x scale square
+fn write_png(grid: &[Vec<i64>], path: &str, scale: usize) -> Result<(), png::EncodingError> {
+    let width = grid[0].len() * scale;
+    let height = grid.len() * scale;
+    let mut data = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            data.push(if grid[y / scale][x / scale] > 0 {
+                0
+            } else {
+                255
+            });
+        }
+    }
+    let file = std::fs::File::create(path)?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(&data)
+}
+
+fn print_grid(grid: &[Vec<i64>]) {
+    for row in grid {
+        for cell in row {
+            if *cell > 0 {
+                print!("{}", cell);
+            } else {
+                print!(".");
+            }
+        }
+        println!();
+    }
 }
 
 #[cfg(test)]
@@ -166,7 +439,120 @@ mod tests {
     #[test]
     fn test() {
         let input = parse(include_str!("../../input/14_example.txt"));
+        assert_eq!(
+            infer_arena(&input),
+            Arena {
+                width: 11,
+                height: 7
+            }
+        );
         assert_eq!(part1(&input, 11, 7), 12);
         assert_eq!(part2(&input, 11, 7), 0); // No picture emerges in the test example
     }
+
+    #[test]
+    fn test_arena() {
+        let raw = "size=20x10\np=0,4 v=3,-3\np=19,9 v=1,1\n";
+        let input = parse(raw);
+        assert_eq!(input.len(), 2);
+        let arena = parse_arena(raw).unwrap().unwrap();
+        assert_eq!(
+            arena,
+            Arena {
+                width: 20,
+                height: 10
+            }
+        );
+        assert_eq!(validate_arena(&input, &arena), Ok(()));
+        assert!(validate_arena(&input, &parse_size("19x10").unwrap()).is_err());
+        assert!(parse_size("19").is_err());
+        assert_eq!(
+            parse_arena(include_str!("../../input/14_example.txt")),
+            Ok(None)
+        );
+        assert_eq!(
+            parse_arena("size=20xten\np=0,4 v=3,-3\n"),
+            Err("invalid height: ten".to_string())
+        );
+    }
+
+    // 25 robots with different velocities that line up into a 5x5 block at time t
+    fn block_robots(width: i64, height: i64, t: i64) -> Input {
+        block_robots_at(width, height, t, t)
+    }
+
+    // Robots lined up in x at time tx and in y at time ty, which only form the block when tx == ty
+    fn block_robots_at(width: i64, height: i64, tx: i64, ty: i64) -> Input {
+        (0..25)
+            .map(|k| {
+                let (vx, vy) = (k % 7 - 3 + k / 7, 2 * k % 5 - 2 + k);
+                let (x, y) = (12 + k % 5, 10 + k / 5);
+                Robot {
+                    x: (x - vx * tx).rem_euclid(width),
+                    y: (y - vy * ty).rem_euclid(height),
+                    vx,
+                    vy,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(crt(2, 3, 3, 5), Some((8, 15)));
+        assert_eq!(crt(1, 4, 3, 6), Some((9, 12)));
+        assert_eq!(crt(1, 4, 2, 6), None);
+        assert_eq!(crt(7569 % 101, 101, 7569 % 103, 103), Some((7569, 10403)));
+
+        // same robots as test_detectors, on coprime and non-coprime arenas
+        for (width, height) in [(31, 29), (30, 24)] {
+            let input = block_robots(width, height, 17);
+            assert_eq!(part2_crt(&input, width, height), 17);
+        }
+
+        // 17 and 4 differ mod gcd(30, 24) = 6, so the alignments never coincide and part2_crt falls back to the
+        // best y alignment among the times with the best x alignment
+        let (width, height) = (30, 24);
+        let input = block_robots_at(width, height, 17, 4);
+        let tx = best_axis_time(&input, width, |r, t| (r.x + r.vx * t).rem_euclid(width));
+        let ty = best_axis_time(&input, height, |r, t| (r.y + r.vy * t).rem_euclid(height));
+        assert_eq!((tx, ty), (17, 4));
+        assert_eq!(crt(tx, width, ty, height), None);
+        let t = part2_crt(&input, width, height);
+        assert!(t % width == 17 && t < 120);
+        let y_variance = |t: i64| {
+            let values: Vec<i64> = input
+                .iter()
+                .map(|r| (r.y + r.vy * t).rem_euclid(height))
+                .collect();
+            axis_variance(&values)
+        };
+        for other in [17, 47, 77, 107] {
+            assert!(y_variance(t) <= y_variance(other));
+        }
+    }
+
+    #[test]
+    fn test_detectors() {
+        let (width, height, t) = (31, 29, 17);
+        let input = block_robots(width, height, t);
+        for detector in [
+            Detector::Variance,
+            Detector::LargestComponent,
+            Detector::RunLength,
+        ] {
+            assert_eq!(
+                find_picture(&input, width, height, detector),
+                t,
+                "{:?}",
+                detector
+            );
+        }
+
+        let grid = grid_at(&input, width, height, t);
+        assert_eq!(largest_component(&grid), 25);
+        let pbm = to_pbm(&grid);
+        assert!(pbm.starts_with("P1\n31 29\n"));
+        assert_eq!(pbm.matches('1').count(), 25 + 2); // plus the two 1s in the header
+    }
 }
@@ -14,10 +14,67 @@ then go onto the next. And then actually move the boxes all at the end (Or maybe
 */
 
 use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 
 fn main() {
     let input = parse(include_str!("../../input/15.txt"));
 
+    // Optional replay export, e.g. `--wide --stop-at 100 --replay-text out.txt --replay-ppm frames/ --scale 8`
+    let args: Vec<String> = std::env::args().collect();
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .map(|k| args.get(k + 1).expect("missing argument value").clone())
+    };
+    let replay_text = arg_value("--replay-text");
+    let replay_ppm = arg_value("--replay-ppm");
+    if replay_text.is_some() || replay_ppm.is_some() {
+        let stop_at = arg_value("--stop-at").map(|n| n.parse().unwrap());
+        let scale = arg_value("--scale").map_or(1, |n| n.parse().unwrap());
+        let grid = if args.iter().any(|a| a == "--wide") {
+            scale_up(&input.grid)
+        } else {
+            input.grid.clone()
+        };
+        // frames are written as they're made rather than kept, there are 20001 of them
+        let mut text = replay_text.map(|path| BufWriter::new(File::create(path).unwrap()));
+        if let Some(dir) = &replay_ppm {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        let frames = stop_at.unwrap_or(input.moves.len()).min(input.moves.len()) + 1;
+        let digits = frames.to_string().len();
+        let grid = record_replay(&grid, &input.moves, stop_at, |frame| {
+            if let Some(out) = &mut text {
+                out.write_all(frame.to_text().as_bytes())?;
+            }
+            if let Some(dir) = &replay_ppm {
+                frame.write_ppm(dir, digits, scale)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+        if let Some(mut out) = text {
+            out.flush().unwrap();
+        }
+        print_grid(&grid);
+        return;
+    }
+
+    // Opt-in validation mode: check warehouse invariants after every move of both parts
+    if args.iter().any(|a| a == "--validate") {
+        for grid in [input.grid.clone(), scale_up(&input.grid)] {
+            match simulate_checked(&grid, &input.moves, step) {
+                Ok(grid) => println!("ok: {}", gps_sum(&grid)),
+                Err(violation) => {
+                    print!("{}", violation.diff());
+                    std::process::exit(1);
+                }
+            }
+        }
+        return;
+    }
+
     let part1 = part1(&input);
     println!("Part 1: {}", part1);
     assert_eq!(part1, 1478649);
@@ -77,181 +134,143 @@ fn simple_push(grid: &mut Vec<Vec<char>>, i: &mut i64, j: &mut i64, di: i64, dj:
     }
 }
 
-fn part1(input: &Input) -> i64 {
-    let mut grid = input.grid.clone();
+fn move_delta(m: char) -> (i64, i64) {
+    match m {
+        '<' => (0, -1),
+        '>' => (0, 1),
+        '^' => (-1, 0),
+        'v' => (1, 0),
+        _ => panic!("invalid move"),
+    }
+}
 
-    let mut i = -1;
-    let mut j = -1;
-    for (i1, row) in grid.iter().enumerate() {
-        for (j1, cell) in row.iter().enumerate() {
+fn find_robot(grid: &[Vec<char>]) -> (i64, i64) {
+    for (i, row) in grid.iter().enumerate() {
+        for (j, cell) in row.iter().enumerate() {
             if *cell == '@' {
-                i = i1 as i64;
-                j = j1 as i64;
-                break;
+                return (i as i64, j as i64);
             }
         }
     }
-    assert_ne!(i, -1);
-    assert_ne!(j, -1);
-
-    for m in &input.moves {
-        // println!("{:?}", m);
-        let (di, dj) = match m {
-            '<' => (0, -1),
-            '>' => (0, 1),
-            '^' => (-1, 0),
-            'v' => (1, 0),
-            _ => panic!("invalid move"),
-        };
-
-        // Look ahead until we find a wall or edge of the grid
-        simple_push(&mut grid, &mut i, &mut j, di, dj);
-    }
-
-    // print grid
-    for i in 0..grid.len() {
-        for j in 0..grid[0].len() {
-            print!("{}", grid[i][j]);
-        }
-        println!();
-    }
+    panic!("no robot in grid");
+}
 
-    let mut sum = 0;
+fn scale_up(grid: &[Vec<char>]) -> Vec<Vec<char>> {
+    let mut wide = vec![vec![' '; grid[0].len() * 2]; grid.len()];
     for i in 0..grid.len() {
         for j in 0..grid[0].len() {
-            if grid[i][j] == 'O' {
-                sum += i * 100 + j;
-            }
-        }
-    }
-    sum as i64
-}
-
-fn part2(input: &Input) -> i64 {
-    // Start by scaling up grid
-    let mut grid = vec![vec![' '; input.grid[0].len() * 2]; input.grid.len()];
-    for i in 0..input.grid.len() {
-        for j in 0..input.grid[0].len() {
-            let new = match input.grid[i][j] {
+            let new = match grid[i][j] {
                 '#' => "##",
                 '.' => "..",
                 'O' => "[]",
                 '@' => "@.",
                 _ => panic!("invalid cell"),
             };
-            grid[i][2 * j] = new.chars().nth(0).unwrap();
-            grid[i][2 * j + 1] = new.chars().nth(1).unwrap();
+            wide[i][2 * j] = new.chars().nth(0).unwrap();
+            wide[i][2 * j + 1] = new.chars().nth(1).unwrap();
         }
     }
+    wide
+}
 
-    for m in &input.moves {
-        // print_grid(&grid);
-        // println!("{:?}", m);
-        let (di, dj) = match m {
-            '<' => (0, -1),
-            '>' => (0, 1),
-            '^' => (-1, 0),
-            'v' => (1, 0),
-            _ => panic!("invalid move"),
-        };
+fn wide_push(grid: &mut Vec<Vec<char>>, i: i64, j: i64, di: i64, dj: i64) {
+    // Let's just assume that the push suceeds, and revert it if it doesn't
+    let mut grid1 = grid.clone();
 
-        let mut i = -1;
-        let mut j = -1;
-        for (i1, row) in grid.iter().enumerate() {
-            for (j1, cell) in row.iter().enumerate() {
-                if *cell == '@' {
-                    i = i1 as i64;
-                    j = j1 as i64;
-                    break;
-                }
-            }
-        }
-        assert_ne!(i, -1);
-        assert_ne!(j, -1);
+    // queue of cells to move
+    let mut queue = VecDeque::new();
+    let mut pushed = vec![vec![false; grid[0].len()]; grid.len()];
+    let mut is_failed = false;
+    grid1[i as usize][j as usize] = '.';
+    queue.push_back((i + di, j + dj, '@'));
 
-        if m == &'<' || m == &'>' {
-            // Look ahead until we find a wall or edge of the grid
-            simple_push(&mut grid, &mut i, &mut j, di, dj);
-            continue;
+    while !queue.is_empty() {
+        let (i, j, new_content) = queue.pop_front().unwrap();
+        // println!("{:?} {:?} {:?}", i, j, new_content);
+        if i < 0 || i >= grid.len() as i64 || j < 0 || j >= grid[0].len() as i64 {
+            // hit edge of grid - failed
+            is_failed = true;
+            break;
         }
-
-        // Let's just assume that the push suceeds, and revert it if it doesn't
-        let mut grid1 = grid.clone();
-
-        // queue of cells to move
-        let mut queue = VecDeque::new();
-        let mut pushed = vec![vec![false; grid[0].len()]; grid.len()];
-        let mut is_failed = false;
-        grid1[i as usize][j as usize] = '.';
-        queue.push_back((i + di, j + dj, '@'));
-
-        while !queue.is_empty() {
-            let (i, j, new_content) = queue.pop_front().unwrap();
-            // println!("{:?} {:?} {:?}", i, j, new_content);
-            if i < 0 || i >= grid.len() as i64 || j < 0 || j >= grid[0].len() as i64 {
-                // hit edge of grid - failed
+        let cell = grid[i as usize][j as usize];
+        match cell {
+            '#' => {
+                // failed
                 is_failed = true;
                 break;
             }
-            let cell = grid[i as usize][j as usize];
-            match cell {
-                '#' => {
-                    // failed
-                    is_failed = true;
-                    break;
-                }
-                '.' => {
-                    // empty cell
-                    grid1[i as usize][j as usize] = new_content;
+            '.' => {
+                // empty cell
+                grid1[i as usize][j as usize] = new_content;
+            }
+            'O' => {
+                // single width box (unscaled warehouse)
+                if !pushed[(i + di) as usize][(j + dj) as usize] {
+                    queue.push_back((i + di, j + dj, 'O'));
+                    pushed[(i + di) as usize][(j + dj) as usize] = true;
                 }
-                '[' => {
-                    // then g[i][j] is a box and also g[i][j+1] is a box
-                    assert_eq!(grid[i as usize][j as usize + 1], ']');
-                    let left_pushed = pushed[(i + di) as usize][(j + dj) as usize];
-                    let right_pushed = pushed[(i + di) as usize][(j + dj + 1) as usize];
-                    assert_eq!(left_pushed, right_pushed);
-                    if !left_pushed {
-                        queue.push_back((i + di, j + dj, '['));
-                        queue.push_back((i + di, j + dj + 1, ']'));
-                        if !pushed[i as usize][j as usize + 1] {
-                            grid1[i as usize][j as usize + 1] = '.';
-                        }
-                        pushed[(i + di) as usize][(j + dj) as usize] = true;
-                        pushed[(i + di) as usize][(j + dj + 1) as usize] = true;
+                grid1[i as usize][j as usize] = new_content;
+            }
+            '[' => {
+                // then g[i][j] is a box and also g[i][j+1] is a box
+                assert_eq!(grid[i as usize][j as usize + 1], ']');
+                let left_pushed = pushed[(i + di) as usize][(j + dj) as usize];
+                let right_pushed = pushed[(i + di) as usize][(j + dj + 1) as usize];
+                assert_eq!(left_pushed, right_pushed);
+                if !left_pushed {
+                    queue.push_back((i + di, j + dj, '['));
+                    queue.push_back((i + di, j + dj + 1, ']'));
+                    if !pushed[i as usize][j as usize + 1] {
+                        grid1[i as usize][j as usize + 1] = '.';
                     }
-                    grid1[i as usize][j as usize] = new_content;
+                    pushed[(i + di) as usize][(j + dj) as usize] = true;
+                    pushed[(i + di) as usize][(j + dj + 1) as usize] = true;
                 }
-                ']' => {
-                    // then g[i][j] is a box and also g[i][j-1] is a box
-                    assert_eq!(grid[i as usize][j as usize - 1], '[');
-                    let left_pushed = pushed[(i + di) as usize][(j + dj - 1) as usize];
-                    let right_pushed = pushed[(i + di) as usize][(j + dj) as usize];
-                    assert_eq!(left_pushed, right_pushed);
-                    if !left_pushed {
-                        queue.push_back((i + di, j + dj, ']'));
-                        queue.push_back((i + di, j + dj - 1, '['));
-                        if !pushed[i as usize][j as usize - 1] {
-                            grid1[i as usize][j as usize - 1] = '.';
-                        }
-                        pushed[(i + di) as usize][(j + dj - 1) as usize] = true;
-                        pushed[(i + di) as usize][(j + dj) as usize] = true;
+                grid1[i as usize][j as usize] = new_content;
+            }
+            ']' => {
+                // then g[i][j] is a box and also g[i][j-1] is a box
+                assert_eq!(grid[i as usize][j as usize - 1], '[');
+                let left_pushed = pushed[(i + di) as usize][(j + dj - 1) as usize];
+                let right_pushed = pushed[(i + di) as usize][(j + dj) as usize];
+                assert_eq!(left_pushed, right_pushed);
+                if !left_pushed {
+                    queue.push_back((i + di, j + dj, ']'));
+                    queue.push_back((i + di, j + dj - 1, '['));
+                    if !pushed[i as usize][j as usize - 1] {
+                        grid1[i as usize][j as usize - 1] = '.';
                     }
-                    grid1[i as usize][j as usize] = new_content;
+                    pushed[(i + di) as usize][(j + dj - 1) as usize] = true;
+                    pushed[(i + di) as usize][(j + dj) as usize] = true;
                 }
-                _ => panic!("invalid cell"),
+                grid1[i as usize][j as usize] = new_content;
             }
+            _ => panic!("invalid cell"),
         }
+    }
 
-        if !is_failed {
-            grid = grid1;
-        }
+    if !is_failed {
+        *grid = grid1;
     }
-    print_grid(&grid);
+}
+
+// Apply a single robot move. Works for both the normal and the scaled up warehouse
+fn step(grid: &mut Vec<Vec<char>>, m: char) {
+    let (di, dj) = move_delta(m);
+    let (mut i, mut j) = find_robot(grid);
+    if di == 0 {
+        // horizontal pushes are always just a row of cells
+        simple_push(grid, &mut i, &mut j, di, dj);
+    } else {
+        wide_push(grid, i, j, di, dj);
+    }
+}
 
+fn gps_sum(grid: &[Vec<char>]) -> i64 {
     let mut sum = 0;
     for i in 0..grid.len() {
         for j in 0..grid[0].len() {
-            if grid[i][j] == '[' {
+            if grid[i][j] == 'O' || grid[i][j] == '[' {
                 sum += i * 100 + j;
             }
         }
@@ -259,6 +278,194 @@ fn part2(input: &Input) -> i64 {
     sum as i64
 }
 
+fn part1(input: &Input) -> i64 {
+    let mut grid = input.grid.clone();
+    for m in &input.moves {
+        step(&mut grid, *m);
+    }
+    print_grid(&grid);
+    gps_sum(&grid)
+}
+
+fn part2(input: &Input) -> i64 {
+    // Start by scaling up grid
+    let mut grid = scale_up(&input.grid);
+    for m in &input.moves {
+        // print_grid(&grid);
+        // println!("{:?}", m);
+        step(&mut grid, *m);
+    }
+    print_grid(&grid);
+    gps_sum(&grid)
+}
+
+// Snapshot of the warehouse after a move. Frame 0 is the starting grid and has no move
+#[derive(Debug, Clone)]
+struct Frame<'a> {
+    move_index: usize,
+    m: Option<char>,
+    grid: &'a [Vec<char>],
+}
+
+impl Frame<'_> {
+    // text animation block, with a header line
+    fn to_text(&self) -> String {
+        let mut out = match self.m {
+            Some(m) => format!("Move {} {}:\n", self.move_index, m),
+            None => "Initial state:\n".to_string(),
+        };
+        for row in self.grid {
+            out.extend(row.iter());
+            out.push('\n');
+        }
+        out.push('\n');
+        out
+    }
+
+    // binary PPM in `dir`, named by move number padded to `digits`
+    fn write_ppm(&self, dir: &str, digits: usize, scale: usize) -> std::io::Result<()> {
+        let path = format!("{}/{:0width$}.ppm", dir, self.move_index, width = digits);
+        std::fs::write(path, grid_to_ppm(self.grid, scale))
+    }
+}
+
+// Run the moves, handing each frame to `on_frame` as soon as it's made so only the current grid is kept. If `stop_at`
+// is set, stop after that many moves. Returns the final grid
+fn record_replay(
+    grid: &[Vec<char>],
+    moves: &[char],
+    stop_at: Option<usize>,
+    mut on_frame: impl FnMut(&Frame) -> std::io::Result<()>,
+) -> std::io::Result<Vec<Vec<char>>> {
+    let mut grid = grid.to_vec();
+    on_frame(&Frame {
+        move_index: 0,
+        m: None,
+        grid: &grid,
+    })?;
+    let n = stop_at.unwrap_or(moves.len()).min(moves.len());
+    for (k, m) in moves[..n].iter().enumerate() {
+        step(&mut grid, *m);
+        on_frame(&Frame {
+            move_index: k + 1,
+            m: Some(*m),
+            grid: &grid,
+        })?;
+    }
+    Ok(grid)
+}
+
+fn cell_colour(cell: char) -> [u8; 3] {
+    match cell {
+        '#' => [64, 64, 64],
+        '.' => [255, 255, 255],
+        'O' | '[' | ']' => [200, 140, 40],
+        '@' => [220, 30, 30],
+        _ => [0, 0, 255],
+    }
+}
+
+fn grid_to_ppm(grid: &[Vec<char>], scale: usize) -> Vec<u8> {
+    let height = grid.len() * scale;
+    let width = grid[0].len() * scale;
+    let mut out = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    for y in 0..height {
+        for x in 0..width {
+            out.extend(cell_colour(grid[y / scale][x / scale]));
+        }
+    }
+    out
+}
+
+// first move that broke an invariant, with the grid before and after
+#[derive(Debug)]
+struct Violation {
+    move_index: usize,
+    m: char,
+    reason: String,
+    before: Vec<Vec<char>>,
+    after: Vec<Vec<char>>,
+}
+
+impl Violation {
+    // before and after side by side, changed rows marked
+    fn diff(&self) -> String {
+        let mut out = format!("Move {} {}: {}\n", self.move_index, self.m, self.reason);
+        let mut changed = vec![];
+        for (i, (row0, row1)) in self.before.iter().zip(&self.after).enumerate() {
+            let before: String = row0.iter().collect();
+            let after: String = row1.iter().collect();
+            let marker = if row0 != row1 { " <" } else { "" };
+            out.push_str(&format!("{}   {}{}\n", before, after, marker));
+            for (j, (c0, c1)) in row0.iter().zip(row1).enumerate() {
+                if c0 != c1 {
+                    changed.push(format!("({}, {}) {} -> {}", i, j, c0, c1));
+                }
+            }
+        }
+        out.push_str(&format!("changed cells: {}\n", changed.join(", ")));
+        out
+    }
+}
+
+fn count_boxes(grid: &[Vec<char>]) -> usize {
+    grid.iter()
+        .flatten()
+        .filter(|c| **c == 'O' || **c == '[')
+        .count()
+}
+
+// Check the grid after a move against the starting grid
+fn check_invariants(start: &[Vec<char>], grid: &[Vec<char>]) -> Result<(), String> {
+    let boxes0 = count_boxes(start);
+    let boxes1 = count_boxes(grid);
+    if boxes0 != boxes1 {
+        return Err(format!("box count changed from {} to {}", boxes0, boxes1));
+    }
+    let robots = grid.iter().flatten().filter(|c| **c == '@').count();
+    if robots != 1 {
+        return Err(format!("expected exactly one robot, found {}", robots));
+    }
+    for (i, row) in grid.iter().enumerate() {
+        for (j, cell) in row.iter().enumerate() {
+            if (start[i][j] == '#') != (*cell == '#') {
+                return Err(format!("wall changed at ({}, {})", i, j));
+            }
+            if *cell == '[' && row.get(j + 1) != Some(&']') {
+                return Err(format!("unpaired [ at ({}, {})", i, j));
+            }
+            if *cell == ']' && (j == 0 || row[j - 1] != '[') {
+                return Err(format!("unpaired ] at ({}, {})", i, j));
+            }
+        }
+    }
+    Ok(())
+}
+
+// run the moves with `step`, checking invariants after every move
+fn simulate_checked(
+    grid: &[Vec<char>],
+    moves: &[char],
+    step: fn(&mut Vec<Vec<char>>, char),
+) -> Result<Vec<Vec<char>>, Violation> {
+    let start = grid.to_vec();
+    let mut grid = grid.to_vec();
+    for (k, m) in moves.iter().enumerate() {
+        let before = grid.clone();
+        step(&mut grid, *m);
+        if let Err(reason) = check_invariants(&start, &grid) {
+            return Err(Violation {
+                move_index: k + 1,
+                m: *m,
+                reason,
+                before,
+                after: grid,
+            });
+        }
+    }
+    Ok(grid)
+}
+
 fn print_grid(grid: &Vec<Vec<char>>) {
     for i in 0..grid.len() {
         for j in 0..grid[0].len() {
@@ -278,4 +485,71 @@ mod tests {
         assert_eq!(part1(&input), 10092);
         assert_eq!(part2(&input), 9021);
     }
+
+    #[test]
+    fn test_replay() {
+        let input = parse(include_str!("../../input/15_example.txt"));
+        let grid = scale_up(&input.grid);
+        let mut frames = 0;
+        let last = record_replay(&grid, &input.moves, None, |_| {
+            frames += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(frames, input.moves.len() + 1);
+        assert_eq!(gps_sum(&last), 9021);
+
+        let mut text = String::new();
+        let mut moves = vec![];
+        record_replay(&grid, &input.moves, Some(5), |frame| {
+            text.push_str(&frame.to_text());
+            moves.push(frame.m);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(moves.len(), 6);
+        assert_eq!(moves[5], Some(input.moves[4]));
+        assert!(text.starts_with("Initial state:\n####################\n"));
+        assert_eq!(text.matches("Move ").count(), 5);
+
+        // errors from the frame handler stop the replay
+        let mut frames = 0;
+        let error = record_replay(&grid, &input.moves, None, |_| {
+            frames += 1;
+            match frames {
+                3 => Err(std::io::Error::other("disk full")),
+                _ => Ok(()),
+            }
+        });
+        assert!(error.is_err());
+        assert_eq!(frames, 3);
+
+        let ppm = grid_to_ppm(&grid, 2);
+        assert!(ppm.starts_with(b"P6\n40 20\n255\n"));
+        assert_eq!(ppm.len(), "P6\n40 20\n255\n".len() + 40 * 20 * 3);
+    }
+
+    #[test]
+    fn test_invariants() {
+        let input = parse(include_str!("../../input/15_example.txt"));
+        let grid = simulate_checked(&input.grid, &input.moves, step).unwrap();
+        assert_eq!(gps_sum(&grid), 10092);
+        let grid = simulate_checked(&scale_up(&input.grid), &input.moves, step).unwrap();
+        assert_eq!(gps_sum(&grid), 9021);
+
+        // the original part 2 bug: the cell a box left is cleared after another box already moved into it
+        fn buggy_step(grid: &mut Vec<Vec<char>>, m: char) {
+            let (i, j) = find_robot(grid);
+            step(grid, m);
+            let (di, dj) = move_delta(m);
+            let (i2, j2) = ((i + 2 * di) as usize, (j + 2 * dj) as usize);
+            if di != 0 && grid[i2][j2] == '[' {
+                grid[i2][j2] = '.';
+            }
+        }
+        let violation =
+            simulate_checked(&scale_up(&input.grid), &input.moves, buggy_step).unwrap_err();
+        assert_eq!(violation.reason, "box count changed from 21 to 20");
+        assert!(violation.diff().contains("changed cells:"));
+    }
 }
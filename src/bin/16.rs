@@ -5,25 +5,35 @@ https://adventofcode.com/2024/day/16
 part1:
 straightforward dijkstra. vertices are (i, j, dir) where i, j are the current position and dir is the direction we are
 facing. Edges are moving forward, turning left or right. The cost of moving forward is 1, turning left or right is 1000.
+(These costs live in CostModel, so variants with other costs, U-turns or weighted terrain can use the same solver.)
 
 part2:
 Easiest way to solve this is to run a forward dijkstra from the start point and a backwards dijkstra from the end point.
 Then, for each cell, we check if there is a path that goes through that cell. If there is, we increment the count.
 
+The same two distance tables give a DAG of the edges on some best path, which best_paths walks to list the paths
+themselves (and count them).
+
 */
 
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashMap};
 
 fn main() {
     let input = parse(include_str!("../../input/16.txt"));
 
-    let part1 = part1(&input);
+    let part1 = part1(&input).expect("no path from S to E");
     println!("Part 1: {}", part1);
     assert_eq!(part1, 135512);
 
     let part2 = part2(&input);
     println!("Part 2: {}", part2);
     assert_eq!(part2, 541);
+
+    let paths = best_paths(&input, &CostModel::default());
+    println!("Distinct best paths: {}", paths.num_paths());
+    for path in paths.take(3) {
+        println!("{} moves, {} turns", path.moves.len(), path.turns.len());
+    }
 }
 
 type Input = Vec<Vec<char>>;
@@ -49,152 +59,333 @@ fn get_pos(input: &Input, c: char) -> (i64, i64) {
 
 const DIRECTIONS: [(i64, i64); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
 
-fn part1(input: &Input) -> i64 {
-    // dijkstra
-    let (start_i, start_j) = get_pos(input, 'S');
-    let start_dir = 1;
+// (i, j, dir)
+type State = (i64, i64, usize);
 
-    let mut heap = BinaryHeap::new();
-    let mut dist = vec![vec![vec![std::i64::MAX; 4]; input[0].len()]; input.len()];
-    let mut visited = vec![vec![vec![false; 4]; input[0].len()]; input.len()];
-    heap.push((0, start_i, start_j, start_dir));
+fn in_bounds(input: &Input, i: i64, j: i64) -> bool {
+    i >= 0 && i < input.len() as i64 && j >= 0 && j < input[0].len() as i64
+}
 
-    loop {
-        let (cost, i, j, dir) = heap.pop().unwrap();
-        if visited[i as usize][j as usize][dir as usize] {
-            continue;
-        }
-        visited[i as usize][j as usize][dir as usize] = true;
-        dist[i as usize][j as usize][dir as usize] = cost;
+// costs of the moves, tiles in `terrain` cost that much to step onto
+#[derive(Debug, Clone)]
+struct CostModel {
+    step: i64,
+    left_turn: i64,
+    right_turn: i64,
+    // turning around on the spot in one move, if allowed
+    u_turn: Option<i64>,
+    terrain: HashMap<char, i64>,
+}
 
-        if input[i as usize][j as usize] == 'E' {
-            return -cost;
+impl Default for CostModel {
+    fn default() -> Self {
+        CostModel {
+            step: 1,
+            left_turn: 1000,
+            right_turn: 1000,
+            u_turn: None,
+            terrain: HashMap::new(),
         }
+    }
+}
 
-        // edges: forward
-        let i1 = i + DIRECTIONS[dir as usize].0;
-        let j1 = j + DIRECTIONS[dir as usize].1;
-        if i1 >= 0 && i1 < input.len() as i64 && j1 >= 0 && j1 < input[0].len() as i64 {
-            if input[i1 as usize][j1 as usize] != '#' {
-                heap.push((cost - 1, i1, j1, dir));
+impl CostModel {
+    // every cost must be positive, free moves mean infinitely many best paths
+    fn validate(&self) -> Result<(), String> {
+        let costs = [
+            ("step", Some(self.step)),
+            ("left turn", Some(self.left_turn)),
+            ("right turn", Some(self.right_turn)),
+            ("u-turn", self.u_turn),
+        ];
+        for (name, cost) in costs {
+            if let Some(cost) = cost.filter(|&cost| cost <= 0) {
+                return Err(format!("{} cost must be positive, got {}", name, cost));
             }
         }
-        // edges: turn left or right
-        let left_dir = (dir + 3) % 4;
-        let right_dir = (dir + 1) % 4;
-        heap.push((cost - 1000, i, j, left_dir));
-        heap.push((cost - 1000, i, j, right_dir));
+        for (tile, &cost) in &self.terrain {
+            if cost <= 0 {
+                return Err(format!("cost of '{}' must be positive, got {}", tile, cost));
+            }
+        }
+        Ok(())
+    }
+
+    fn enter_cost(&self, tile: char) -> i64 {
+        *self.terrain.get(&tile).unwrap_or(&self.step)
     }
 }
 
-fn part2(input: &Input) -> i64 {
-    // Forward dijkstra
-    let dist_from_start = {
-        let (start_i, start_j) = get_pos(input, 'S');
-        let (end_i, end_j) = get_pos(input, 'E');
-        let start_dir = 1;
-        let mut heap = BinaryHeap::new();
-        let mut dist = vec![vec![vec![std::i64::MAX; 4]; input[0].len()]; input.len()];
-        let mut visited = vec![vec![vec![false; 4]; input[0].len()]; input.len()];
-        heap.push((0, start_i, start_j, start_dir));
-
-        while heap.len() > 0 {
-            let (cost, i, j, dir) = heap.pop().unwrap();
-            if visited[i as usize][j as usize][dir as usize] {
-                continue;
-            }
-            visited[i as usize][j as usize][dir as usize] = true;
-            dist[i as usize][j as usize][dir as usize] = cost;
-
-            // edges: forward
-            let i1 = i + DIRECTIONS[dir as usize].0;
-            let j1 = j + DIRECTIONS[dir as usize].1;
-            if i1 >= 0 && i1 < input.len() as i64 && j1 >= 0 && j1 < input[0].len() as i64 {
-                if input[i1 as usize][j1 as usize] != '#' {
-                    heap.push((cost - 1, i1, j1, dir));
-                }
-            }
-            // edges: turn left or right
-            let left_dir = (dir + 3) % 4;
-            let right_dir = (dir + 1) % 4;
-            let is_end_point = (i, j) == (end_i, end_j);
-            let rotate_cost = if is_end_point { 0 } else { 1000 };
-            heap.push((cost - rotate_cost, i, j, left_dir));
-            heap.push((cost - rotate_cost, i, j, right_dir));
+// Edges out of a state: move forward, turn left, right or around. The path is over once it reaches E
+fn successors(input: &Input, costs: &CostModel, (i, j, dir): State) -> Vec<(char, State, i64)> {
+    if input[i as usize][j as usize] == 'E' {
+        return vec![];
+    }
+    let mut edges = vec![];
+    let i1 = i + DIRECTIONS[dir].0;
+    let j1 = j + DIRECTIONS[dir].1;
+    if in_bounds(input, i1, j1) && input[i1 as usize][j1 as usize] != '#' {
+        let cost = costs.enter_cost(input[i1 as usize][j1 as usize]);
+        edges.push(('F', (i1, j1, dir), cost));
+    }
+    edges.push(('L', (i, j, (dir + 3) % 4), costs.left_turn));
+    edges.push(('R', (i, j, (dir + 1) % 4), costs.right_turn));
+    if let Some(cost) = costs.u_turn {
+        edges.push(('U', (i, j, (dir + 2) % 4), cost));
+    }
+    edges
+}
+
+// Edges into a state, i.e. the successor edges reversed
+fn predecessors(input: &Input, costs: &CostModel, (i, j, dir): State) -> Vec<(State, i64)> {
+    let mut edges = vec![];
+    let i1 = i - DIRECTIONS[dir].0;
+    let j1 = j - DIRECTIONS[dir].1;
+    if in_bounds(input, i1, j1) && !matches!(input[i1 as usize][j1 as usize], '#' | 'E') {
+        edges.push((
+            (i1, j1, dir),
+            costs.enter_cost(input[i as usize][j as usize]),
+        ));
+    }
+    if input[i as usize][j as usize] != 'E' {
+        // turning left from dir + 1 or right from dir + 3 ends up facing dir
+        edges.push(((i, j, (dir + 1) % 4), costs.left_turn));
+        edges.push(((i, j, (dir + 3) % 4), costs.right_turn));
+        if let Some(cost) = costs.u_turn {
+            edges.push(((i, j, (dir + 2) % 4), cost));
         }
-        dist
-    };
-
-    // Backwards dijkstra
-    let dist_from_end = {
-        let (start_i, start_j) = get_pos(input, 'E');
-        let start_dir = 1;
-        let mut heap = BinaryHeap::new();
-        let mut dist = vec![vec![vec![std::i64::MAX; 4]; input[0].len()]; input.len()];
-        let mut visited = vec![vec![vec![false; 4]; input[0].len()]; input.len()];
-        heap.push((0, start_i, start_j, start_dir));
-
-        while heap.len() > 0 {
-            let (cost, i, j, dir) = heap.pop().unwrap();
-            if visited[i as usize][j as usize][dir as usize] {
-                continue;
-            }
-            visited[i as usize][j as usize][dir as usize] = true;
-            dist[i as usize][j as usize][dir as usize] = cost;
-
-            // edges: forward (backwards)
-            let i1 = i - DIRECTIONS[dir as usize].0;
-            let j1 = j - DIRECTIONS[dir as usize].1;
-            if i1 >= 0 && i1 < input.len() as i64 && j1 >= 0 && j1 < input[0].len() as i64 {
-                if input[i1 as usize][j1 as usize] != '#' {
-                    heap.push((cost - 1, i1, j1, dir));
-                }
+    }
+    edges
+}
+
+// Cost from the start states to every state, i64::MAX if unreachable
+fn dijkstra(
+    input: &Input,
+    starts: &[State],
+    edges: impl Fn(State) -> Vec<(State, i64)>,
+) -> Vec<Vec<Vec<i64>>> {
+    let mut heap = BinaryHeap::new();
+    let mut dist = vec![vec![vec![i64::MAX; 4]; input[0].len()]; input.len()];
+    for &(i, j, dir) in starts {
+        heap.push((0, i, j, dir));
+    }
+
+    while let Some((cost, i, j, dir)) = heap.pop() {
+        let cost = -cost;
+        if dist[i as usize][j as usize][dir] != i64::MAX {
+            continue;
+        }
+        dist[i as usize][j as usize][dir] = cost;
+        for ((i1, j1, dir1), edge_cost) in edges((i, j, dir)) {
+            if dist[i1 as usize][j1 as usize][dir1] == i64::MAX {
+                heap.push((-(cost + edge_cost), i1, j1, dir1));
             }
-            // edges: turn left or right
-            let left_dir = (dir + 3) % 4;
-            let right_dir = (dir + 1) % 4;
-            let is_end_point = (i, j) == (start_i, start_j);
-            let rotate_cost = if is_end_point { 0 } else { 1000 };
-            heap.push((cost - rotate_cost, i, j, left_dir));
-            heap.push((cost - rotate_cost, i, j, right_dir));
         }
-        dist
-    };
+    }
+    dist
+}
+
+struct Solution {
+    costs: CostModel,
+    dist_from_start: Vec<Vec<Vec<i64>>>,
+    dist_to_end: Vec<Vec<Vec<i64>>>,
+    // None if E can't be reached
+    distance: Option<i64>,
+}
+
+fn solve(input: &Input, costs: &CostModel) -> Solution {
+    if let Err(e) = costs.validate() {
+        panic!("invalid cost model: {}", e);
+    }
     let (start_i, start_j) = get_pos(input, 'S');
     let (end_i, end_j) = get_pos(input, 'E');
-    let distance = dist_from_start[end_i as usize][end_j as usize][0];
-    println!("Distance: {}", distance);
-    assert_eq!(
+    let dist_from_start = dijkstra(input, &[(start_i, start_j, 1)], |s| {
+        successors(input, costs, s)
+            .into_iter()
+            .map(|(_, t, cost)| (t, cost))
+            .collect()
+    });
+    let ends: Vec<State> = (0..4).map(|dir| (end_i, end_j, dir)).collect();
+    let dist_to_end = dijkstra(input, &ends, |s| predecessors(input, costs, s));
+    let distance = *dist_from_start[end_i as usize][end_j as usize]
+        .iter()
+        .min()
+        .unwrap();
+    assert_eq!(distance, dist_to_end[start_i as usize][start_j as usize][1]);
+    let distance = (distance != i64::MAX).then_some(distance);
+    Solution {
+        costs: costs.clone(),
+        dist_from_start,
+        dist_to_end,
         distance,
-        dist_from_end[start_i as usize][start_j as usize][1]
-    );
+    }
+}
+
+impl Solution {
+    fn on_best_path(&self, (i, j, dir): State) -> bool {
+        let a = self.dist_from_start[i as usize][j as usize][dir];
+        let b = self.dist_to_end[i as usize][j as usize][dir];
+        a != i64::MAX && b != i64::MAX && Some(a + b) == self.distance
+    }
+
+    // Successor edges that stay on some best path
+    fn best_successors(&self, input: &Input, s: State) -> Vec<(char, State)> {
+        let cost = self.dist_from_start[s.0 as usize][s.1 as usize][s.2];
+        successors(input, &self.costs, s)
+            .into_iter()
+            .filter(|(_, t, c)| {
+                self.on_best_path(*t)
+                    && self.dist_from_start[t.0 as usize][t.1 as usize][t.2] == cost + c
+            })
+            .map(|(m, t, _)| (m, t))
+            .collect()
+    }
+
+    fn best_tiles(&self, input: &Input) -> Vec<Vec<bool>> {
+        (0..input.len())
+            .map(|i| {
+                (0..input[i].len())
+                    .map(|j| (0..4).any(|dir| self.on_best_path((i as i64, j as i64, dir))))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+fn part1(input: &Input) -> Option<i64> {
+    solve(input, &CostModel::default()).distance
+}
 
-    let mut count = 0;
+fn part2(input: &Input) -> i64 {
+    let solution = solve(input, &CostModel::default());
+    println!("Distance: {:?}", solution.distance);
+    let tiles = solution.best_tiles(input);
+    print!("{}", render_tiles(input, &tiles));
+    tiles.iter().flatten().filter(|t| **t).count() as i64
+}
+
+// Maze with the given tiles marked as O, like the puzzle text
+fn render_tiles(input: &Input, tiles: &[Vec<bool>]) -> String {
+    let mut out = String::new();
     for i in 0..input.len() {
         for j in 0..input[i].len() {
-            let mut best_path = false;
-            for dir in 0..4 {
-                if dist_from_start[i][j][dir] == std::i64::MAX
-                    || dist_from_end[i][j][dir] == std::i64::MAX
-                {
-                    continue;
-                }
-                if dist_from_start[i][j][dir] + dist_from_end[i][j][dir] == distance {
-                    best_path = true;
-                }
+            out.push(if tiles[i][j] { 'O' } else { input[i][j] });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// one best path, moves are F (forward), L, R and U (turn on the spot)
+#[derive(Debug, Clone, PartialEq)]
+struct BestPath {
+    moves: String,
+    // every tile visited, from S to E
+    tiles: Vec<(i64, i64)>,
+    // where the path turns, and which way
+    turns: Vec<(i64, i64, char)>,
+}
+
+// lazy DFS over the best-path edges, use .take(n) to limit
+struct BestPaths<'a> {
+    input: &'a Input,
+    solution: Solution,
+    // current DFS path: state and the index of the next successor to try
+    stack: Vec<(State, usize)>,
+    moves: Vec<char>,
+}
+
+fn best_paths<'a>(input: &'a Input, costs: &CostModel) -> BestPaths<'a> {
+    let (start_i, start_j) = get_pos(input, 'S');
+    BestPaths {
+        input,
+        solution: solve(input, costs),
+        stack: vec![((start_i, start_j, 1), 0)],
+        moves: vec![],
+    }
+}
+
+impl BestPaths<'_> {
+    fn current_path(&self) -> BestPath {
+        let mut tiles = vec![];
+        let mut turns = vec![];
+        for (k, ((i, j, _), _)) in self.stack.iter().enumerate() {
+            if tiles.last() != Some(&(*i, *j)) {
+                tiles.push((*i, *j));
             }
-            if best_path {
-                count += 1;
+            if let Some(m @ ('L' | 'R' | 'U')) = self.moves.get(k) {
+                turns.push((*i, *j, *m));
             }
-            if best_path {
-                print!("O");
+        }
+        BestPath {
+            moves: self.moves.iter().collect(),
+            tiles,
+            turns,
+        }
+    }
+
+    // count over the best-path DAG in order of distance
+    fn num_paths(&self) -> u128 {
+        let input = self.input;
+        let solution = &self.solution;
+        let mut states = vec![];
+        for (i, row) in input.iter().enumerate() {
+            for j in 0..row.len() {
+                for dir in 0..4 {
+                    let s = (i as i64, j as i64, dir);
+                    if solution.on_best_path(s) {
+                        states.push(s);
+                    }
+                }
+            }
+        }
+        states.sort_by_key(|s| {
+            std::cmp::Reverse(solution.dist_from_start[s.0 as usize][s.1 as usize][s.2])
+        });
+
+        let mut paths = std::collections::HashMap::new();
+        for s in states {
+            let n: u128 = if input[s.0 as usize][s.1 as usize] == 'E' {
+                1
             } else {
-                print!("{}", input[i][j]);
+                solution
+                    .best_successors(input, s)
+                    .iter()
+                    .map(|(_, t)| paths[t])
+                    .sum()
+            };
+            paths.insert(s, n);
+        }
+        // the start isn't on a best path when there isn't one
+        let (start_i, start_j) = get_pos(input, 'S');
+        paths.get(&(start_i, start_j, 1)).copied().unwrap_or(0)
+    }
+}
+
+impl Iterator for BestPaths<'_> {
+    type Item = BestPath;
+
+    fn next(&mut self) -> Option<BestPath> {
+        while let Some(&(state, k)) = self.stack.last() {
+            let next = self.solution.best_successors(self.input, state);
+            if k < next.len() {
+                self.stack.last_mut().unwrap().1 += 1;
+                let (m, t) = next[k];
+                self.moves.push(m);
+                self.stack.push((t, 0));
+                if self.input[t.0 as usize][t.1 as usize] == 'E' {
+                    let path = self.current_path();
+                    self.stack.pop();
+                    self.moves.pop();
+                    return Some(path);
+                }
+            } else {
+                self.stack.pop();
+                self.moves.pop();
             }
         }
-        println!();
+        None
     }
-    count
 }
 
 #[cfg(test)]
@@ -204,14 +395,125 @@ mod tests {
     #[test]
     fn test() {
         let input = parse(include_str!("../../input/16_example.txt"));
-        assert_eq!(part1(&input), 7036);
+        assert_eq!(part1(&input), Some(7036));
         assert_eq!(part2(&input), 45);
     }
 
+    #[test]
+    fn test_costs() {
+        let input = parse("#######\n#.....#\n#S~~~E#\n#######\n");
+        let default = CostModel::default();
+        assert_eq!(solve(&input, &default).distance, Some(4));
+        let mut swamp = CostModel::default();
+        swamp.terrain.insert('~', 1000);
+        assert_eq!(solve(&input, &swamp).distance, Some(3001));
+        swamp.terrain.insert('~', 2000);
+        assert_eq!(solve(&input, &swamp).distance, Some(3006));
+        let path = best_paths(&input, &swamp).next().unwrap();
+        assert_eq!(path.moves, "LFRFFFFRF");
+
+        let input = parse("#####\n#E.S#\n#####\n");
+        assert_eq!(solve(&input, &default).distance, Some(2002));
+        let cheap_left = CostModel {
+            left_turn: 1,
+            ..CostModel::default()
+        };
+        assert_eq!(solve(&input, &cheap_left).distance, Some(4));
+        let u_turn = CostModel {
+            u_turn: Some(500),
+            ..CostModel::default()
+        };
+        assert_eq!(solve(&input, &u_turn).distance, Some(502));
+        let path = best_paths(&input, &u_turn).next().unwrap();
+        assert_eq!(path.moves, "UFF");
+        assert_eq!(path.turns, vec![(1, 3, 'U')]);
+        assert_eq!(best_paths(&input, &default).num_paths(), 2);
+    }
+
+    #[test]
+    fn test_no_path() {
+        let input = parse("#####\n#S#E#\n#####\n");
+        assert_eq!(part1(&input), None);
+        assert_eq!(part2(&input), 0);
+        assert_eq!(best_paths(&input, &CostModel::default()).num_paths(), 0);
+        assert_eq!(best_paths(&input, &CostModel::default()).next(), None);
+    }
+
+    #[test]
+    fn test_cost_validation() {
+        assert_eq!(CostModel::default().validate(), Ok(()));
+        let free_turns = CostModel {
+            left_turn: 0,
+            right_turn: 0,
+            ..CostModel::default()
+        };
+        assert_eq!(
+            free_turns.validate(),
+            Err("left turn cost must be positive, got 0".to_string())
+        );
+        let u_turn = CostModel {
+            u_turn: Some(-1),
+            ..CostModel::default()
+        };
+        assert_eq!(
+            u_turn.validate(),
+            Err("u-turn cost must be positive, got -1".to_string())
+        );
+        let mut free_swamp = CostModel::default();
+        free_swamp.terrain.insert('~', 0);
+        assert_eq!(
+            free_swamp.validate(),
+            Err("cost of '~' must be positive, got 0".to_string())
+        );
+    }
+
+    // free turns would mean spinning on the spot forever without changing the cost
+    #[test]
+    #[should_panic(expected = "invalid cost model: left turn cost must be positive")]
+    fn test_free_turns() {
+        let input = parse("#####\n#S.E#\n#####\n");
+        let free_turns = CostModel {
+            left_turn: 0,
+            right_turn: 0,
+            ..CostModel::default()
+        };
+        best_paths(&input, &free_turns).num_paths();
+    }
+
+    #[test]
+    fn test_best_paths() {
+        let input = parse(include_str!("../../input/16_example.txt"));
+        let paths: Vec<BestPath> = best_paths(&input, &CostModel::default()).collect();
+        assert_eq!(
+            paths.len() as u128,
+            best_paths(&input, &CostModel::default()).num_paths()
+        );
+        assert_eq!(best_paths(&input, &CostModel::default()).take(1).count(), 1);
+        assert_eq!(paths.len(), 3);
+
+        // every path costs the best distance and together they cover the O tiles
+        let mut tiles = vec![vec![false; input[0].len()]; input.len()];
+        for path in &paths {
+            let forward = path.moves.matches('F').count() as i64;
+            assert_eq!(forward + 1000 * path.turns.len() as i64, 7036);
+            assert_eq!(path.tiles.len() as i64, forward + 1);
+            for (i, j) in &path.tiles {
+                tiles[*i as usize][*j as usize] = true;
+            }
+        }
+        assert_eq!(tiles.iter().flatten().filter(|t| **t).count(), 45);
+        let render = render_tiles(&input, &tiles);
+        assert!(render.starts_with("###############\n#.......#....O#\n"));
+    }
+
     #[test]
     fn test2() {
         let input = parse(include_str!("../../input/16_example2.txt"));
-        assert_eq!(part1(&input), 11048);
+        assert_eq!(part1(&input), Some(11048));
         assert_eq!(part2(&input), 64);
+        assert_eq!(
+            best_paths(&input, &CostModel::default()).num_paths(),
+            best_paths(&input, &CostModel::default()).count() as u128
+        );
     }
 }
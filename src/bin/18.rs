@@ -13,15 +13,35 @@ a dfs after each new obstacle is added.
 */
 
 fn main() {
-    let input = parse(include_str!("../../input/18.txt"));
+    let raw_input = include_str!("../../input/18.txt");
+    let input = parse(raw_input);
+    let size = parse_size(raw_input)
+        .unwrap()
+        .unwrap_or_else(|| infer_size(&input));
+    assert_eq!(size, 71);
 
-    let part1 = part1(&input, 71, 1024);
+    let part1 = part1(&input, size, 1024);
     println!("Part 1: {}", part1);
     assert_eq!(part1, 310);
 
-    let part2 = part2(&input, 71);
+    let part2 = part2(&input, size);
     println!("Part 2: {}", part2);
     assert_eq!(part2, "16,46");
+
+    let t = first_blocking_byte(&input, size).unwrap();
+    assert_eq!(format!("{},{}", input[t].x, input[t].y), part2);
+    assert_eq!(first_blocking_byte_bisect(&input, size), Some(t));
+
+    let timeline = Timeline::new(&input, size);
+    assert_eq!(timeline.path_length(1024), Some(part1));
+    assert!(timeline.path_length(t).is_some() && timeline.path_length(t + 1).is_none());
+
+    // `--chart out.csv` writes path length against number of fallen bytes
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(k) = args.iter().position(|a| a == "--chart") {
+        let path = args.get(k + 1).expect("missing argument value");
+        std::fs::write(path, timeline.to_csv()).unwrap();
+    }
 }
 
 #[derive(Debug)]
@@ -35,7 +55,7 @@ type Input = Vec<Coord>;
 fn parse(input: &str) -> Input {
     input
         .lines()
-        .filter(|line| !line.is_empty())
+        .filter(|line| !line.is_empty() && !line.starts_with("size="))
         .map(|line| {
             let mut parts = line.split(",");
             let x = parts.next().unwrap().parse().unwrap();
@@ -45,6 +65,21 @@ fn parse(input: &str) -> Input {
         .collect()
 }
 
+// Optional `size=71` header line
+fn parse_size(input: &str) -> Result<Option<usize>, String> {
+    let Some(size) = input.lines().next().and_then(|l| l.strip_prefix("size=")) else {
+        return Ok(None);
+    };
+    size.parse()
+        .map(Some)
+        .map_err(|_| format!("invalid size: {}", size))
+}
+
+// Smallest square memory space holding every byte
+fn infer_size(input: &Input) -> usize {
+    input.iter().map(|c| c.x.max(c.y)).max().unwrap() as usize + 1
+}
+
 // fn print_grid(grid: &Vec<Vec<i64>>) {
 //     for row in grid {
 //         for cell in row {
@@ -145,6 +180,215 @@ fn part2(input: &Input, size: usize) -> String {
     unreachable!();
 }
 
+// union-find over the grid cells, path halving and union by size
+struct DisjointSet {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(n: usize) -> Self {
+        DisjointSet {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+        }
+    }
+
+    fn find(&mut self, mut a: usize) -> usize {
+        while self.parent[a] != a {
+            self.parent[a] = self.parent[self.parent[a]];
+            a = self.parent[a];
+        }
+        a
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+}
+
+// Offline version of part 2: let every byte fall, then take them away again in reverse order, joining each freed cell
+// to its free neighbours. The first time the corners become connected, the byte just removed is the blocking one.
+// Returns None if the exit is still reachable after all the bytes
+fn first_blocking_byte(input: &[Coord], size: usize) -> Option<usize> {
+    let cell = |x: i64, y: i64| y as usize * size + x as usize;
+    // a byte only frees its cell when it's the first byte to land there
+    let mut first_byte = vec![usize::MAX; size * size];
+    for (t, coord) in input.iter().enumerate() {
+        let c = cell(coord.x, coord.y);
+        first_byte[c] = first_byte[c].min(t);
+    }
+
+    let mut sets = DisjointSet::new(size * size);
+    let is_free = |c: usize, fallen: usize| first_byte[c] >= fallen;
+    let join_neighbours = |sets: &mut DisjointSet, x: i64, y: i64, fallen: usize| {
+        for (dx, dy) in DIRECTIONS.iter() {
+            let x1 = x + dx;
+            let y1 = y + dy;
+            if x1 >= 0
+                && x1 < size as i64
+                && y1 >= 0
+                && y1 < size as i64
+                && is_free(cell(x1, y1), fallen)
+            {
+                sets.union(cell(x, y), cell(x1, y1));
+            }
+        }
+    };
+    let end = cell(size as i64 - 1, size as i64 - 1);
+
+    // everything fallen
+    for y in 0..size as i64 {
+        for x in 0..size as i64 {
+            if is_free(cell(x, y), input.len()) {
+                join_neighbours(&mut sets, x, y, input.len());
+            }
+        }
+    }
+    if sets.find(0) == sets.find(end) {
+        return None;
+    }
+    for t in (0..input.len()).rev() {
+        let coord = &input[t];
+        if first_byte[cell(coord.x, coord.y)] != t {
+            continue;
+        }
+        // now only the first t bytes have fallen
+        join_neighbours(&mut sets, coord.x, coord.y, t);
+        if sets.find(0) == sets.find(end) {
+            return Some(t);
+        }
+    }
+    unreachable!("the exit is reachable before any bytes fall");
+}
+
+// Same answer by binary searching for the shortest prefix of bytes that blocks the exit
+fn first_blocking_byte_bisect(input: &[Coord], size: usize) -> Option<usize> {
+    let blocked = |fallen: usize| {
+        let mut grid = vec![vec![0; size]; size];
+        for coord in &input[..fallen] {
+            grid[coord.y as usize][coord.x as usize] = 2;
+        }
+        !path_exists(&grid)
+    };
+    if !blocked(input.len()) {
+        return None;
+    }
+    // blocked(lo) is false, blocked(hi) is true
+    let (mut lo, mut hi) = (0, input.len());
+    while hi - lo > 1 {
+        let mid = (lo + hi) / 2;
+        if blocked(mid) {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    Some(hi - 1)
+}
+
+// BFS from the top left corner. Returns the path length and the cells on one shortest path
+fn shortest_path(grid: &[Vec<i64>]) -> Option<(i64, Vec<(i64, i64)>)> {
+    let n = grid.len();
+    let mut prev = vec![vec![None; n]; n];
+    let mut queue = std::collections::VecDeque::new();
+    prev[0][0] = Some((0, 0));
+    queue.push_back((0i64, 0i64));
+    while let Some((x, y)) = queue.pop_front() {
+        if x == n as i64 - 1 && y == n as i64 - 1 {
+            let mut path = vec![(x, y)];
+            while *path.last().unwrap() != (0, 0) {
+                let (x, y) = *path.last().unwrap();
+                path.push(prev[y as usize][x as usize].unwrap());
+            }
+            return Some((path.len() as i64 - 1, path));
+        }
+        for (dx, dy) in DIRECTIONS.iter() {
+            let x1 = x + dx;
+            let y1 = y + dy;
+            if x1 >= 0
+                && x1 < n as i64
+                && y1 >= 0
+                && y1 < n as i64
+                && grid[y1 as usize][x1 as usize] == 0
+                && prev[y1 as usize][x1 as usize].is_none()
+            {
+                prev[y1 as usize][x1 as usize] = Some((x, y));
+                queue.push_back((x1, y1));
+            }
+        }
+    }
+    None
+}
+
+// Shortest path length after t bytes have fallen, for every t from 0 to input.len(). A new byte only changes the
+// answer if it lands on the current path, so the BFS only needs re-running then
+fn path_length_timeline(input: &[Coord], size: usize) -> Vec<Option<i64>> {
+    let mut grid = vec![vec![0; size]; size];
+    let mut on_path = vec![vec![false; size]; size];
+    let mut current = update_path(&grid, &mut on_path);
+
+    let mut timeline = vec![current];
+    for coord in input {
+        grid[coord.y as usize][coord.x as usize] = 2;
+        if on_path[coord.y as usize][coord.x as usize] {
+            current = update_path(&grid, &mut on_path);
+        }
+        timeline.push(current);
+    }
+    timeline
+}
+
+// shortest path length against the number of fallen bytes
+struct Timeline {
+    lengths: Vec<Option<i64>>,
+}
+
+impl Timeline {
+    fn new(input: &[Coord], size: usize) -> Self {
+        Timeline {
+            lengths: path_length_timeline(input, size),
+        }
+    }
+
+    // Path length once `fallen` bytes have landed, None if the exit is unreachable. Past the end of the input
+    // nothing else falls
+    fn path_length(&self, fallen: usize) -> Option<i64> {
+        self.lengths[fallen.min(self.lengths.len() - 1)]
+    }
+
+    fn to_csv(&self) -> String {
+        let mut out = String::from("fallen,length\n");
+        for (t, len) in self.lengths.iter().enumerate() {
+            match len {
+                Some(len) => out.push_str(&format!("{},{}\n", t, len)),
+                None => out.push_str(&format!("{},unreachable\n", t)),
+            }
+        }
+        out
+    }
+}
+
+// Re-run the BFS, marking the cells of the new shortest path
+fn update_path(grid: &[Vec<i64>], on_path: &mut [Vec<bool>]) -> Option<i64> {
+    for row in on_path.iter_mut() {
+        row.fill(false);
+    }
+    let (len, path) = shortest_path(grid)?;
+    for (x, y) in path {
+        on_path[y as usize][x as usize] = true;
+    }
+    Some(len)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -152,7 +396,58 @@ mod tests {
     #[test]
     fn test() {
         let input = parse(include_str!("../../input/18_example.txt"));
+        assert_eq!(infer_size(&input), 7);
         assert_eq!(part1(&input, 7, 12), 22);
         assert_eq!(part2(&input, 7), "6,1");
     }
+
+    #[test]
+    fn test_offline() {
+        let input = parse(include_str!("../../input/18_example.txt"));
+        assert_eq!(first_blocking_byte(&input, 7), Some(20));
+        assert_eq!(first_blocking_byte_bisect(&input, 7), Some(20));
+        assert_eq!(first_blocking_byte(&input[..20], 7), None);
+        assert_eq!(first_blocking_byte_bisect(&input[..20], 7), None);
+
+        let timeline = path_length_timeline(&input, 7);
+        assert_eq!(timeline.len(), input.len() + 1);
+        assert_eq!(timeline[0], Some(12));
+        assert_eq!(timeline[12], Some(22));
+        assert_eq!(timeline[20], timeline[19]);
+        assert!(timeline[20].is_some());
+        assert_eq!(timeline[21], None);
+        for t in [0, 5, 12, 20, 21, 25] {
+            let mut grid = vec![vec![0; 7]; 7];
+            for coord in &input[..t] {
+                grid[coord.y as usize][coord.x as usize] = 2;
+            }
+            assert_eq!(timeline[t], shortest_path(&grid).map(|(len, _)| len));
+        }
+    }
+
+    #[test]
+    fn test_timeline() {
+        let raw = "size=4\n0,1\n1,1\n2,1\n";
+        let input = parse(raw);
+        assert_eq!(input.len(), 3);
+        assert_eq!(parse_size(raw), Ok(Some(4)));
+        assert_eq!(parse_size("0,1\n"), Ok(None));
+        assert_eq!(
+            parse_size("size=four\n0,1\n"),
+            Err("invalid size: four".to_string())
+        );
+        assert_eq!(infer_size(&input), 3);
+
+        let timeline = Timeline::new(&input, 4);
+        assert_eq!(timeline.path_length(0), Some(6));
+        assert_eq!(timeline.path_length(3), Some(6));
+        assert_eq!(timeline.path_length(100), Some(6));
+        let timeline = Timeline::new(&input, 3);
+        assert_eq!(timeline.path_length(2), Some(4));
+        assert_eq!(timeline.path_length(3), None);
+        assert_eq!(
+            timeline.to_csv(),
+            "fallen,length\n0,4\n1,4\n2,4\n3,unreachable\n"
+        );
+    }
 }
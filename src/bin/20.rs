@@ -9,8 +9,18 @@ part 1. I went straight for a double dijkstra solution.
 part 2:
 double dijsktra solution generalizes easily to part 2
 
+It's O(cells * radius^2) though. Since the race is a single track there's a way that doesn't depend on the radius
+(count_cheats_track_order). With track indices i, j and rotated coordinates u = x + y, v = x - y, a cheat saves
+(j - i) - d where d = max(|du|, |dv|), so it saves at least T when d <= R and |du|, |dv| <= j - i - T. Every step along
+the track changes u and v by exactly 1, so u - j and v - j never go up and u + j, v + j never go down. That turns the
+second condition into j >= J(i) for a J found by binary search, and J(i) never goes down as i goes up. So walking i
+backwards, add each j >= J(i) to a 2D Fenwick tree over (u, v) and count the ones in the square d <= R around i.
+O(track log track + track log^2 size).
+
 */
 
+use std::collections::BTreeMap;
+
 fn main() {
     let input = parse(include_str!("../../input/20.txt"));
 
@@ -21,6 +31,28 @@ fn main() {
     let part2 = part2(&input, 20, 100);
     println!("Part 2: {}", part2);
     assert_eq!(part2, 993178);
+
+    assert_eq!(count_cheats(&input, 2, 100, Algorithm::TrackOrder), part1);
+    assert_eq!(count_cheats(&input, 20, 100, Algorithm::TrackOrder), part2);
+
+    // e.g. `--radius 500` to count cheats for much larger radii
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(k) = args.iter().position(|a| a == "--radius") {
+        let radius = args
+            .get(k + 1)
+            .expect("missing argument value")
+            .parse()
+            .unwrap();
+        let count = count_cheats(&input, radius, 100, Algorithm::TrackOrder);
+        println!(
+            "Cheats up to {} picoseconds saving >= 100: {}",
+            radius, count
+        );
+    }
+
+    if args.iter().any(|a| a == "--histogram") {
+        print!("{}", format_histogram(&histogram(&cheats(&input, 20, 100))));
+    }
 }
 
 type Input = Vec<Vec<char>>;
@@ -73,6 +105,19 @@ fn part1(input: &Input, threshold: i64) -> i64 {
 }
 
 fn part2(input: &Input, max_cheat_dist: i64, threshold: i64) -> i64 {
+    count_cheats(input, max_cheat_dist, threshold, Algorithm::BruteForce)
+}
+
+// cheat from `start` to `end` (track positions) and the time it saves
+#[derive(Debug, Clone, PartialEq)]
+struct Cheat {
+    start: (usize, usize),
+    end: (usize, usize),
+    saving: i64,
+}
+
+// Every cheat of at most max_cheat_dist picoseconds saving at least threshold
+fn cheats(input: &Input, max_cheat_dist: i64, threshold: i64) -> Vec<Cheat> {
     let start = get_pos(input, 'S');
     let end = get_pos(input, 'E');
 
@@ -82,7 +127,7 @@ fn part2(input: &Input, max_cheat_dist: i64, threshold: i64) -> i64 {
     let time = dist[end.1][end.0];
     assert_eq!(time, dist_end[start.1][start.0]);
 
-    let mut count = 0;
+    let mut cheats = vec![];
     for y in 0..input.len() {
         for x in 0..input[0].len() {
             if dist[y][x] == std::i64::MAX {
@@ -103,7 +148,11 @@ fn part2(input: &Input, max_cheat_dist: i64, threshold: i64) -> i64 {
                                 dist[y][x] + dist_end[y1][x1] + cheat_x.abs() + cheat_y.abs();
                             let saving = time - route;
                             if saving >= threshold {
-                                count += 1;
+                                cheats.push(Cheat {
+                                    start: (x, y),
+                                    end: (x1, y1),
+                                    saving,
+                                });
                             }
                         }
                     }
@@ -112,9 +161,165 @@ fn part2(input: &Input, max_cheat_dist: i64, threshold: i64) -> i64 {
         }
     }
 
+    cheats
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Algorithm {
+    // try every offset within the radius from every track cell. O(cells * radius^2)
+    BruteForce,
+    // Count pairs of cells along the track with a Fenwick tree. O(track log^2), whatever the radius
+    TrackOrder,
+}
+
+fn count_cheats(input: &Input, max_cheat_dist: i64, threshold: i64, algorithm: Algorithm) -> i64 {
+    match algorithm {
+        Algorithm::BruteForce => cheats(input, max_cheat_dist, threshold).len() as i64,
+        Algorithm::TrackOrder => count_cheats_track_order(input, max_cheat_dist, threshold),
+    }
+}
+
+// Track cells in race order. The race is a single track so the distance from the start is the index
+fn track(input: &Input) -> Vec<(usize, usize)> {
+    let dist = dijkstra(input, get_pos(input, 'S'));
+    let mut cells = vec![];
+    for (y, row) in dist.iter().enumerate() {
+        for (x, d) in row.iter().enumerate() {
+            if *d != i64::MAX {
+                cells.push((*d, (x, y)));
+            }
+        }
+    }
+    cells.sort();
+    for (k, (d, _)) in cells.iter().enumerate() {
+        assert_eq!(*d, k as i64, "race track is not a single path");
+    }
+    cells.into_iter().map(|(_, pos)| pos).collect()
+}
+
+// Counts every cheat the brute force does, for any radius and threshold, see the header
+fn count_cheats_track_order(input: &Input, max_cheat_dist: i64, threshold: i64) -> i64 {
+    let track = track(input);
+    let height = input.len() as i64;
+    // u = x + y is in 0..size, and v = x - y + height - 1 is too
+    let size = (input[0].len() + input.len()) as i64;
+    let u: Vec<i64> = track.iter().map(|&(x, y)| (x + y) as i64).collect();
+    let v: Vec<i64> = track
+        .iter()
+        .map(|&(x, y)| x as i64 - y as i64 + height - 1)
+        .collect();
+    // u - k and v - k never go up along the track, u + k and v + k never go down
+    let u_minus: Vec<i64> = (0..track.len()).map(|k| u[k] - k as i64).collect();
+    let v_minus: Vec<i64> = (0..track.len()).map(|k| v[k] - k as i64).collect();
+    let u_plus: Vec<i64> = (0..track.len()).map(|k| u[k] + k as i64).collect();
+    let v_plus: Vec<i64> = (0..track.len()).map(|k| v[k] + k as i64).collect();
+
+    let mut fenwick = Fenwick2D::new(size as usize, size as usize);
+    let mut added = track.len();
+    let mut count = 0;
+    for i in (0..track.len()).rev() {
+        // |du| <= j - i - T and |dv| <= j - i - T hold from this j on
+        let first = [
+            u_minus.partition_point(|&a| a > u_minus[i] - threshold),
+            v_minus.partition_point(|&a| a > v_minus[i] - threshold),
+            u_plus.partition_point(|&b| b < u_plus[i] + threshold),
+            v_plus.partition_point(|&b| b < v_plus[i] + threshold),
+        ]
+        .into_iter()
+        .max()
+        .unwrap();
+        while added > first {
+            added -= 1;
+            fenwick.add(u[added], v[added]);
+        }
+        count += fenwick.count(
+            u[i] - max_cheat_dist,
+            u[i] + max_cheat_dist,
+            v[i] - max_cheat_dist,
+            v[i] + max_cheat_dist,
+        );
+    }
     count
 }
 
+// Counts points in rectangles, with points added one at a time
+struct Fenwick2D {
+    width: usize,
+    height: usize,
+    tree: Vec<i64>,
+}
+
+impl Fenwick2D {
+    fn new(width: usize, height: usize) -> Fenwick2D {
+        Fenwick2D {
+            width,
+            height,
+            tree: vec![0; (width + 1) * (height + 1)],
+        }
+    }
+
+    fn add(&mut self, x: i64, y: i64) {
+        let mut i = x as usize + 1;
+        while i <= self.width {
+            let mut j = y as usize + 1;
+            while j <= self.height {
+                self.tree[i * (self.height + 1) + j] += 1;
+                j += j & j.wrapping_neg();
+            }
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    // Points with x < x1 and y < y1
+    fn prefix(&self, x1: i64, y1: i64) -> i64 {
+        let mut sum = 0;
+        let mut i = x1.clamp(0, self.width as i64) as usize;
+        while i > 0 {
+            let mut j = y1.clamp(0, self.height as i64) as usize;
+            while j > 0 {
+                sum += self.tree[i * (self.height + 1) + j];
+                j -= j & j.wrapping_neg();
+            }
+            i -= i & i.wrapping_neg();
+        }
+        sum
+    }
+
+    // Points in x0..=x1, y0..=y1
+    fn count(&self, x0: i64, x1: i64, y0: i64, y1: i64) -> i64 {
+        self.prefix(x1 + 1, y1 + 1) - self.prefix(x0, y1 + 1) - self.prefix(x1 + 1, y0)
+            + self.prefix(x0, y0)
+    }
+}
+
+// Number of cheats for each saving, ignoring cheats that don't save anything
+fn histogram(cheats: &[Cheat]) -> BTreeMap<i64, usize> {
+    let mut counts = BTreeMap::new();
+    for cheat in cheats.iter().filter(|c| c.saving > 0) {
+        *counts.entry(cheat.saving).or_insert(0) += 1;
+    }
+    counts
+}
+
+// Same wording as the puzzle's worked examples
+fn format_histogram(counts: &BTreeMap<i64, usize>) -> String {
+    let mut out = String::new();
+    for (saving, count) in counts {
+        if *count == 1 {
+            out.push_str(&format!(
+                "There is one cheat that saves {} picoseconds.\n",
+                saving
+            ));
+        } else {
+            out.push_str(&format!(
+                "There are {} cheats that save {} picoseconds.\n",
+                count, saving
+            ));
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,4 +330,62 @@ mod tests {
         assert_eq!(part1(&input, 20), 5);
         assert_eq!(part2(&input, 20, 70), 41);
     }
+
+    #[test]
+    fn test_track_order() {
+        let input = parse(include_str!("../../input/20_example.txt"));
+        assert_eq!(track(&input).len(), 85);
+        for radius in [0, 1, 2, 3, 20, 50, 100] {
+            for threshold in [-20, -1, 0, 1, 2, 20, 50, 70] {
+                assert_eq!(
+                    count_cheats(&input, radius, threshold, Algorithm::TrackOrder),
+                    count_cheats(&input, radius, threshold, Algorithm::BruteForce),
+                    "radius {} threshold {}",
+                    radius,
+                    threshold
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_histogram() {
+        let input = parse(include_str!("../../input/20_example.txt"));
+        let part1 = "\
+There are 14 cheats that save 2 picoseconds.
+There are 14 cheats that save 4 picoseconds.
+There are 2 cheats that save 6 picoseconds.
+There are 4 cheats that save 8 picoseconds.
+There are 2 cheats that save 10 picoseconds.
+There are 3 cheats that save 12 picoseconds.
+There is one cheat that saves 20 picoseconds.
+There is one cheat that saves 36 picoseconds.
+There is one cheat that saves 38 picoseconds.
+There is one cheat that saves 40 picoseconds.
+There is one cheat that saves 64 picoseconds.
+";
+        assert_eq!(format_histogram(&histogram(&cheats(&input, 2, 1))), part1);
+
+        let part2 = "\
+There are 32 cheats that save 50 picoseconds.
+There are 31 cheats that save 52 picoseconds.
+There are 29 cheats that save 54 picoseconds.
+There are 39 cheats that save 56 picoseconds.
+There are 25 cheats that save 58 picoseconds.
+There are 23 cheats that save 60 picoseconds.
+There are 20 cheats that save 62 picoseconds.
+There are 19 cheats that save 64 picoseconds.
+There are 12 cheats that save 66 picoseconds.
+There are 14 cheats that save 68 picoseconds.
+There are 12 cheats that save 70 picoseconds.
+There are 22 cheats that save 72 picoseconds.
+There are 4 cheats that save 74 picoseconds.
+There are 3 cheats that save 76 picoseconds.
+";
+        assert_eq!(format_histogram(&histogram(&cheats(&input, 20, 50))), part2);
+
+        let best = cheats(&input, 2, 64);
+        assert_eq!(best.len(), 1);
+        assert_eq!(best[0].saving, 64);
+    }
 }
@@ -10,6 +10,16 @@ fn main() {
     let part2 = part2(&input);
     println!("Part 2: {}", part2);
     assert_eq!(part2, 130470079151124);
+
+    // one optimal part 1 sequence per code, checked by replaying it through the keypads and against a full search
+    let chain = puzzle_chain(2);
+    let mut scope = Scope::new(chain.clone()).unwrap();
+    for code in &input {
+        let human = scope.layers(code).remove(0);
+        assert_eq!(simulate(&chain, &human).unwrap(), *code);
+        assert_eq!(bfs_presses(&chain, code), Some(human.len() as i64));
+        println!("{}: {}", code, human);
+    }
 }
 
 type Input = Vec<String>;
@@ -22,42 +32,145 @@ fn parse(input: &str) -> Input {
         .collect()
 }
 
-lazy_static::lazy_static! {
-    static ref DIRECTIONAL_KEYPAD: Vec<Vec<char>> = vec![
-        vec![' ', '^', 'A'],
-        vec!['<', 'v', '>'],
-    ];
-
-    static ref NUMERIC_KEYPAD: Vec<Vec<char>> = vec![
-        vec!['7', '8', '9'],
-        vec!['4', '5', '6'],
-        vec!['1', '2', '3'],
-        vec![' ', '0', 'A'],
-    ];
-}
-
-fn keypad_loc(keypad: &Vec<Vec<char>>, c: char) -> (usize, usize) {
-    for i in 0..keypad.len() {
-        for j in 0..keypad[i].len() {
-            if keypad[i][j] == c {
-                return (i, j);
+// one row per line, '.' is a gap
+#[derive(Debug, Clone, PartialEq)]
+struct Keypad {
+    keys: Vec<Vec<char>>,
+}
+
+impl Keypad {
+    fn parse(layout: &str) -> Result<Keypad, String> {
+        let keys: Vec<Vec<char>> = layout
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                line.chars()
+                    .map(|c| if c == '.' { ' ' } else { c })
+                    .collect()
+            })
+            .collect();
+        if keys.is_empty() {
+            return Err("empty keypad".to_string());
+        }
+        if keys.iter().any(|row| row.len() != keys[0].len()) {
+            return Err("keypad is not a rectangle".to_string());
+        }
+        let mut seen = std::collections::HashSet::new();
+        for c in keys.iter().flatten().filter(|c| **c != ' ') {
+            if !seen.insert(*c) {
+                return Err(format!("duplicate key {}", c));
             }
         }
+        if !seen.contains(&'A') {
+            return Err("keypad has no A key".to_string());
+        }
+        let keypad = Keypad { keys };
+        // the arm has to be able to reach every key from A without crossing a gap
+        let reachable = keypad.reachable_from('A');
+        if reachable < seen.len() {
+            return Err(format!(
+                "gaps cut off {} keys from A",
+                seen.len() - reachable
+            ));
+        }
+        Ok(keypad)
+    }
+
+    // Number of keys reachable from c, flood filling around the gaps
+    fn reachable_from(&self, c: char) -> usize {
+        let mut seen = vec![vec![false; self.keys[0].len()]; self.keys.len()];
+        let mut stack = vec![self.loc(c)];
+        seen[stack[0].0][stack[0].1] = true;
+        let mut count = 0;
+        while let Some((i, j)) = stack.pop() {
+            count += 1;
+            for (di, dj) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let i1 = (i as i64 + di) as usize;
+                let j1 = (j as i64 + dj) as usize;
+                if i1 < self.keys.len()
+                    && j1 < self.keys[0].len()
+                    && !seen[i1][j1]
+                    && !self.is_gap(i1, j1)
+                {
+                    seen[i1][j1] = true;
+                    stack.push((i1, j1));
+                }
+            }
+        }
+        count
+    }
+
+    fn has_key(&self, c: char) -> bool {
+        c != ' ' && self.keys.iter().flatten().any(|k| *k == c)
+    }
+
+    fn loc(&self, c: char) -> (usize, usize) {
+        for i in 0..self.keys.len() {
+            for j in 0..self.keys[i].len() {
+                if self.keys[i][j] == c {
+                    return (i, j);
+                }
+            }
+        }
+        panic!("Invalid char");
+    }
+
+    fn is_gap(&self, i: usize, j: usize) -> bool {
+        self.keys[i][j] == ' '
     }
-    panic!("Invalid char");
+}
+
+const DIRECTIONAL_LAYOUT: &str = "
+.^A
+<v>
+";
+
+const NUMERIC_LAYOUT: &str = "
+789
+456
+123
+.0A
+";
+
+lazy_static::lazy_static! {
+    static ref DIRECTIONAL_KEYPAD: Keypad = Keypad::parse(DIRECTIONAL_LAYOUT).unwrap();
+    static ref NUMERIC_KEYPAD: Keypad = Keypad::parse(NUMERIC_LAYOUT).unwrap();
+}
+
+// The puzzle's chain: some directional keypads operated by robots, then the numeric keypad on the door
+fn puzzle_chain(directional_robots: usize) -> Vec<Keypad> {
+    let mut chain = vec![DIRECTIONAL_KEYPAD.clone(); directional_robots];
+    chain.push(NUMERIC_KEYPAD.clone());
+    chain
 }
 
 struct Scope {
-    num_keypads: usize,
+    // keypads pressed by the robots, from the one nearest the human to the one on the door.
+    // Keypad r (r >= 1) is chain[r - 1], keypad 0 is the directional keypad the human presses
+    chain: Vec<Keypad>,
     f_cache: HashMap<(usize, char, char), i64>,
 }
 
 impl Scope {
-    fn new(num_keypads: usize) -> Self {
-        Self {
-            num_keypads,
-            f_cache: HashMap::new(),
+    fn new(chain: Vec<Keypad>) -> Result<Self, String> {
+        if chain.is_empty() {
+            return Err("empty keypad chain".to_string());
+        }
+        // every keypad but the last one drives the next robot, so needs all the direction keys
+        for (r, keypad) in chain[..chain.len() - 1].iter().enumerate() {
+            if let Some(c) = "^v<>A".chars().find(|c| !keypad.has_key(*c)) {
+                return Err(format!(
+                    "keypad {} drives a robot but has no {} key",
+                    r + 1,
+                    c
+                ));
+            }
         }
+        Ok(Self {
+            chain,
+            f_cache: HashMap::new(),
+        })
     }
 
     // Number of (human) presses to move keypad r from char x to char y (and enter y on that keypad)
@@ -70,115 +183,182 @@ impl Scope {
             return res;
         }
         // println!("f({}, {}, {})", r, x, y);
-        // robot n-1 is at a numeric keypad
-        // rest of the robots are at directional keypads
+        let keypad = self.chain[r - 1].clone();
 
-        let keypad: &Vec<Vec<char>> = if r == self.num_keypads - 1 {
-            &NUMERIC_KEYPAD
-        } else {
-            &DIRECTIONAL_KEYPAD
-        };
+        let min_presses = candidate_routes(&keypad, x, y)
+            .iter()
+            .map(|route| self.route_presses(r, route))
+            .min()
+            .expect("no route between keys");
 
-        // consider the 2 candidate routes where we go one direction and then the other, and don't go off the edge
-        let (i_start, j_start) = keypad_loc(keypad, x);
-        let (i_end, j_end) = keypad_loc(keypad, y);
-
-        // i first
-        let mut route1 = vec!['A'];
-        let mut route1_failed = false;
-        {
-            let mut i = i_start;
-            let mut j = j_start;
-            while i != i_end {
-                if i < i_end {
-                    route1.push('v');
-                    i += 1;
-                } else if i > i_end {
-                    route1.push('^');
-                    i -= 1;
-                }
-                if keypad[i as usize][j as usize] == ' ' {
-                    route1_failed = true;
-                }
-            }
-            while j != j_end {
-                if j < j_end {
-                    route1.push('>');
-                    j += 1;
-                } else if j > j_end {
-                    route1.push('<');
-                    j -= 1;
-                }
-                if keypad[i as usize][j as usize] == ' ' {
-                    route1_failed = true;
-                }
+        self.f_cache.insert((r, x, y), min_presses);
+        min_presses
+    }
+
+    // Presses to make robot r - 1 type out a route on keypad r. Routes start with the 'A' the arm is resting on
+    fn route_presses(&mut self, r: usize, route: &[char]) -> i64 {
+        let mut presses = 0;
+        for i in 0..route.len() - 1 {
+            presses += self.f(r - 1, route[i], route[i + 1]);
+        }
+        presses
+    }
+
+    // One cheapest route on keypad r from x to y (ending in the press of y), without the leading 'A'
+    fn best_route(&mut self, r: usize, x: char, y: char) -> Vec<char> {
+        let keypad = self.chain[r - 1].clone();
+        let routes = candidate_routes(&keypad, x, y);
+        let best = routes
+            .into_iter()
+            .min_by_key(|route| self.route_presses(r, route))
+            .unwrap();
+        best[1..].to_vec()
+    }
+
+    // keys pressed on every keypad to type `code`, human first
+    fn layers(&mut self, code: &str) -> Vec<String> {
+        let mut layers = vec![code.to_string()];
+        for r in (1..=self.chain.len()).rev() {
+            let mut lower = String::new();
+            let mut pos = 'A';
+            for next in layers.last().unwrap().chars() {
+                lower.extend(self.best_route(r, pos, next));
+                pos = next;
             }
-            route1.push('A');
-        }
-
-        // j first
-        let mut route2 = vec!['A'];
-        let mut route2_failed = false;
-        {
-            let mut i = i_start;
-            let mut j = j_start;
-            while j != j_end {
-                if j < j_end {
-                    route2.push('>');
-                    j += 1;
-                } else if j > j_end {
-                    route2.push('<');
-                    j -= 1;
-                }
-                if keypad[i as usize][j as usize] == ' ' {
-                    route2_failed = true;
+            layers.push(lower);
+        }
+        layers.reverse();
+        layers
+    }
+}
+
+// Every shortest route from x to y that doesn't go over a gap. When a monotone route exists (only moving towards y)
+// these are exactly the monotone routes; otherwise they're the shortest detours round the gaps.
+// Each starts with 'A' (the arm resting on the A of the keypad below) and ends with 'A' to press y
+fn candidate_routes(keypad: &Keypad, x: char, y: char) -> Vec<Vec<char>> {
+    let height = keypad.keys.len();
+    let width = keypad.keys[0].len();
+    let moves = [('^', -1, 0), ('v', 1, 0), ('<', 0, -1), ('>', 0, 1)];
+    let step = |(i, j): (usize, usize), (di, dj): (i64, i64)| {
+        let i1 = (i as i64 + di) as usize;
+        let j1 = (j as i64 + dj) as usize;
+        (i1 < height && j1 < width && !keypad.is_gap(i1, j1)).then_some((i1, j1))
+    };
+
+    // BFS back from y so we know which moves get closer
+    let (i_end, j_end) = keypad.loc(y);
+    let mut dist = vec![vec![usize::MAX; width]; height];
+    dist[i_end][j_end] = 0;
+    let mut queue = std::collections::VecDeque::from([(i_end, j_end)]);
+    while let Some((i, j)) = queue.pop_front() {
+        for (_, di, dj) in moves {
+            if let Some((i1, j1)) = step((i, j), (di, dj)) {
+                if dist[i1][j1] == usize::MAX {
+                    dist[i1][j1] = dist[i][j] + 1;
+                    queue.push_back((i1, j1));
                 }
             }
-            while i != i_end {
-                if i < i_end {
-                    route2.push('v');
-                    i += 1;
-                } else if i > i_end {
-                    route2.push('^');
-                    i -= 1;
-                }
-                if keypad[i as usize][j as usize] == ' ' {
-                    route2_failed = true;
+        }
+    }
+
+    // DFS forward along moves that reduce the distance
+    let mut routes = vec![];
+    let mut stack = vec![(keypad.loc(x), vec!['A'])];
+    while let Some(((i, j), route)) = stack.pop() {
+        if (i, j) == (i_end, j_end) {
+            let mut route = route;
+            route.push('A');
+            routes.push(route);
+            continue;
+        }
+        for (m, di, dj) in moves {
+            if let Some((i1, j1)) = step((i, j), (di, dj)) {
+                if dist[i1][j1] + 1 == dist[i][j] {
+                    let mut route = route.clone();
+                    route.push(m);
+                    stack.push(((i1, j1), route));
                 }
             }
-            route2.push('A');
         }
+    }
+    routes
+}
 
-        let mut min_presses = i64::MAX;
-        if !route1_failed {
-            let mut presses = 0;
-            for i in 0..route1.len() - 1 {
-                presses += self.f(r - 1, route1[i], route1[i + 1]);
+// Human presses a single key. Moves the first robot, or if it's A, presses the first robot's key, which moves the
+// second robot, and so on. Returns the key typed on the last keypad, if any
+fn press(chain: &[Keypad], arms: &mut [(usize, usize)], key: char) -> Result<Option<char>, String> {
+    let mut key = key;
+    for (r, keypad) in chain.iter().enumerate() {
+        let (i, j) = arms[r];
+        let (i1, j1) = match key {
+            '^' => (i.wrapping_sub(1), j),
+            'v' => (i + 1, j),
+            '<' => (i, j.wrapping_sub(1)),
+            '>' => (i, j + 1),
+            'A' => {
+                // press the key under this robot's arm, which passes the press on to the next robot
+                key = keypad.keys[i][j];
+                continue;
             }
-            min_presses = min_presses.min(presses);
+            _ => return Err(format!("invalid key {}", key)),
+        };
+        if i1 >= keypad.keys.len() || j1 >= keypad.keys[0].len() || keypad.is_gap(i1, j1) {
+            return Err(format!("robot {} points at a gap", r + 1));
+        }
+        arms[r] = (i1, j1);
+        return Ok(None);
+    }
+    Ok(Some(key))
+}
+
+// replay the human's presses through the keypads, Err if an arm points at a gap
+fn simulate(chain: &[Keypad], presses: &str) -> Result<String, String> {
+    let mut arms: Vec<(usize, usize)> = chain.iter().map(|keypad| keypad.loc('A')).collect();
+    let mut output = String::new();
+    for (t, c) in presses.chars().enumerate() {
+        let typed = press(chain, &mut arms, c).map_err(|e| format!("press {}: {}", t, e))?;
+        output.extend(typed);
+    }
+    Ok(output)
+}
+
+// Fewest human presses to type the code, by BFS over every arm position of every robot (and how much of the code is
+// typed so far). Exponential in the chain length, but makes no assumptions about routes, so it checks the DP on small
+// chains. Typing a wrong key is a dead end
+fn bfs_presses(chain: &[Keypad], code: &str) -> Option<i64> {
+    let code: Vec<char> = code.chars().collect();
+    let start: Vec<(usize, usize)> = chain.iter().map(|keypad| keypad.loc('A')).collect();
+    let mut seen = std::collections::HashSet::new();
+    let mut queue = std::collections::VecDeque::new();
+    seen.insert((start.clone(), 0));
+    queue.push_back((start, 0, 0));
+    while let Some((arms, typed, presses)) = queue.pop_front() {
+        if typed == code.len() {
+            return Some(presses);
         }
-        if !route2_failed {
-            let mut presses = 0;
-            for i in 0..route2.len() - 1 {
-                presses += self.f(r - 1, route2[i], route2[i + 1]);
+        for key in ['^', 'v', '<', '>', 'A'] {
+            let mut arms1 = arms.clone();
+            let typed1 = match press(chain, &mut arms1, key) {
+                Err(_) => continue,
+                Ok(None) => typed,
+                Ok(Some(c)) if c == code[typed] => typed + 1,
+                Ok(Some(_)) => continue,
+            };
+            if seen.insert((arms1.clone(), typed1)) {
+                queue.push_back((arms1, typed1, presses + 1));
             }
-            min_presses = min_presses.min(presses);
         }
-        // println!("routes: {:?}, {:?}, {} {}", route1, route2, route1_failed, route2_failed);
-        assert!(min_presses != i64::MAX); // 1 of the 2 routes must be valid
-
-        self.f_cache.insert((r, x, y), min_presses);
-        min_presses
     }
+    None
 }
 
-fn solve(code: &str, num_keypads: usize) -> i64 {
+fn solve(code: &str, chain: &[Keypad]) -> i64 {
     let mut presses = 0;
     let mut pos = 'A';
-    let mut x = Scope::new(num_keypads);
+    let mut x = Scope::new(chain.to_vec()).unwrap();
     for i in 0..code.len() {
         let next: char = code.chars().nth(i).unwrap();
-        presses += x.f(num_keypads - 1, pos, next);
+        presses += x.f(chain.len(), pos, next);
         pos = next;
     }
     presses
@@ -189,7 +369,7 @@ fn part1(input: &Input) -> i64 {
     for code in input {
         let len = code.len();
         let numeric_part: i64 = code[..len - 1].to_string().parse().unwrap();
-        let dist = solve(&code, 4);
+        let dist = solve(&code, &puzzle_chain(2));
         println!("{}: {}", code, dist);
         sum += dist * numeric_part;
     }
@@ -201,7 +381,7 @@ fn part2(input: &Input) -> i64 {
     for code in input {
         let len = code.len();
         let numeric_part: i64 = code[..len - 1].to_string().parse().unwrap();
-        let dist = solve(&code, 27);
+        let dist = solve(&code, &puzzle_chain(25));
         println!("{}: {}", code, dist);
         sum += dist * numeric_part;
     }
@@ -218,4 +398,101 @@ mod tests {
         assert_eq!(part1(&input), 126384);
         // assert_eq!(part2(&input), 0);
     }
+
+    #[test]
+    fn test_sequences() {
+        let chain = puzzle_chain(2);
+        let mut scope = Scope::new(chain.clone()).unwrap();
+        let layers = scope.layers("029A");
+        let lens: Vec<usize> = layers.iter().map(|l| l.len()).collect();
+        assert_eq!(lens, vec![68, 28, 12, 4]);
+        assert_eq!(layers[3], "029A");
+        for (k, layer) in layers.iter().enumerate().take(3) {
+            assert_eq!(simulate(&chain[k..], layer).unwrap(), "029A");
+        }
+
+        // every example code, and the human sequence given in the puzzle text
+        for code in parse(include_str!("../../input/21_example.txt")) {
+            let human = &scope.layers(&code)[0];
+            assert_eq!(human.len() as i64, solve(&code, &chain));
+            assert_eq!(simulate(&chain, human).unwrap(), code);
+        }
+        let puzzle = "<vA<AA>>^AvAA<^A>A<v<A>>^AvA^A<vA>^A<v<A>^A>AAvA^A<v<A>A>^AAAvA<^A>A";
+        assert_eq!(simulate(&chain, puzzle).unwrap(), "029A");
+
+        // the door robot starts on A, so going left twice then pressing is the gap
+        assert!(simulate(&puzzle_chain(0), "<<A").is_err());
+        assert!(simulate(&puzzle_chain(0), "^").is_ok());
+    }
+
+    #[test]
+    fn test_exhaustive_routes() {
+        // both L shaped routes from A to 3 go over a gap, only >v> works
+        let zigzag = Keypad::parse("A1.\n.23").unwrap();
+        assert_eq!(
+            candidate_routes(&zigzag, 'A', '3'),
+            vec!["A>v>A".chars().collect::<Vec<_>>()]
+        );
+        // and the only ways from 4 to 5 are detours round the gap
+        let ring = Keypad::parse("123\n4.5\nA76").unwrap();
+        assert_eq!(candidate_routes(&ring, '4', '5').len(), 2);
+        assert_eq!(candidate_routes(&NUMERIC_KEYPAD, '7', 'A').len(), 9);
+
+        let layouts = [NUMERIC_KEYPAD.clone(), zigzag, ring];
+        for layout in layouts {
+            for directional in 0..=2 {
+                let mut chain = vec![DIRECTIONAL_KEYPAD.clone(); directional];
+                chain.push(layout.clone());
+                let mut scope = Scope::new(chain.clone()).unwrap();
+                let keys: String = layout
+                    .keys
+                    .iter()
+                    .flatten()
+                    .filter(|c| **c != ' ')
+                    .collect();
+                for code in [
+                    keys.clone(),
+                    keys.chars().rev().collect(),
+                    "029A".to_string(),
+                ] {
+                    if !code.chars().all(|c| layout.has_key(c)) {
+                        continue;
+                    }
+                    assert_eq!(
+                        Some(solve(&code, &chain)),
+                        bfs_presses(&chain, &code),
+                        "{}",
+                        code
+                    );
+                    let human = &scope.layers(&code)[0];
+                    assert_eq!(simulate(&chain, human).unwrap(), code);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_keypads() {
+        // <A^A>^^AvvvA and v<<A>>^A<A>AvA<^AA>A<vAAA>^A from the puzzle text
+        assert_eq!(solve("029A", &puzzle_chain(0)), 12);
+        assert_eq!(solve("029A", &puzzle_chain(1)), 28);
+        assert_eq!(solve("029A", &puzzle_chain(2)), 68);
+
+        // numeric keypad with the gap in the top left and 7 next to 0: A to 7 is just <<
+        let numeric = Keypad::parse(".89\n456\n123\n70A").unwrap();
+        assert!(numeric.is_gap(0, 0));
+        assert_eq!(solve("7A", std::slice::from_ref(&numeric)), 6);
+        assert_eq!(solve("7A", &[DIRECTIONAL_KEYPAD.clone(), numeric]), 14);
+
+        assert!(Keypad::parse("AB\nC").is_err());
+        assert!(Keypad::parse("AB\nBC").is_err());
+        assert!(Keypad::parse("12\n34").is_err());
+        assert_eq!(
+            Keypad::parse("A.1\nB.2"),
+            Err("gaps cut off 2 keys from A".to_string())
+        );
+        assert!(Keypad::parse("A.1\nB.2\nCDE").is_ok());
+        assert!(Scope::new(vec![NUMERIC_KEYPAD.clone(), NUMERIC_KEYPAD.clone()]).is_err());
+        assert!(Scope::new(puzzle_chain(3)).is_ok());
+    }
 }
@@ -8,6 +8,34 @@ fn main() {
     let part2 = part2(&input);
     println!("Part 2: {}", part2);
     assert_eq!(part2, 1925);
+
+    // e.g. `--top 10 --csv-dir market/` to write the analytics as CSV
+    let args: Vec<String> = std::env::args().collect();
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .map(|k| args.get(k + 1).expect("missing argument value").clone())
+    };
+    if let Some(k) = arg_value("--top") {
+        let buyers: Vec<Buyer> = input.iter().map(|&x| Buyer::new(x, 2000)).collect();
+        let k: usize = k.parse().expect("--top takes a number");
+        assert!(k > 0, "--top must be at least 1");
+        let top = top_sequences(&buyers, k);
+        print!("{}", top_sequences_csv(&top));
+        if let Some(dir) = arg_value("--csv-dir") {
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(format!("{}/series.csv", dir), series_csv(&buyers)).unwrap();
+            std::fs::write(format!("{}/top.csv", dir), top_sequences_csv(&top)).unwrap();
+            // no sequence at all if every buyer has fewer than 4 price changes
+            if let Some((best, _)) = top.first() {
+                std::fs::write(
+                    format!("{}/contributions.csv", dir),
+                    contributions_csv(&buyers, best),
+                )
+                .unwrap();
+            }
+        }
+    }
 }
 
 type Input = Vec<i64>;
@@ -45,49 +73,142 @@ fn part1(input: &Input) -> i64 {
 }
 
 fn part2(input: &Input) -> i64 {
-    let seq_index_max = 19 * 19 * 19 * 19;
-    let mut seq_sum = vec![0; seq_index_max];
-    for i in 0..input.len() {
-        let mut seen = vec![false; seq_index_max];
-        let mut x = input[i];
-        let mut seq = (0, 0, 0, 0);
-        for j in 0..2000 {
-            let next = next(x);
-            let change = ((next % 10) - (x % 10) + 9) as usize; // 0 - 18
-            seq.3 = seq.2;
-            seq.2 = seq.1;
-            seq.1 = seq.0;
-            seq.0 = change;
-            let seq_index = 19 * 19 * 19 * seq.3 + 19 * 19 * seq.2 + 19 * seq.1 + seq.0;
-            x = next;
-
-            if j >= 3 {
-                if !seen[seq_index] {
-                    seen[seq_index] = true;
-                    let value = x % 10;
-                    seq_sum[seq_index] += value;
-                }
-            }
-        }
-    }
+    let buyers: Vec<Buyer> = input.iter().map(|&x| Buyer::new(x, 2000)).collect();
+    let seq_sum = sequence_totals(&buyers);
 
     let mut best = 0;
-    let mut best_seq = (0, 0, 0, 0);
-    for seq_index in 0..seq_index_max {
-        if seq_sum[seq_index] > best {
-            best = seq_sum[seq_index];
-            best_seq = (
-                (seq_index / (19 * 19 * 19)) as i64 - 9,
-                ((seq_index / (19 * 19)) % 19) as i64 - 9,
-                ((seq_index / 19) % 19) as i64 - 9,
-                (seq_index % 19) as i64 - 9,
-            );
+    let mut best_seq = [0; 4];
+    for (seq_index, &total) in seq_sum.iter().enumerate() {
+        if total > best {
+            best = total;
+            best_seq = seq_from_index(seq_index);
         }
     }
     println!("Best: {} @ {:?}", best, best_seq);
     best
 }
 
+const SEQ_INDEX_MAX: usize = 19 * 19 * 19 * 19;
+
+// Each change is -9..=9, so a sequence of 4 is a 4 digit number in base 19
+fn seq_index(seq: &[i64]) -> usize {
+    seq.iter().fold(0, |acc, c| acc * 19 + (c + 9) as usize)
+}
+
+fn seq_from_index(seq_index: usize) -> [i64; 4] {
+    [
+        (seq_index / (19 * 19 * 19)) as i64 - 9,
+        ((seq_index / (19 * 19)) % 19) as i64 - 9,
+        ((seq_index / 19) % 19) as i64 - 9,
+        (seq_index % 19) as i64 - 9,
+    ]
+}
+
+// prices (ones digit of each secret) and the changes between them
+#[derive(Debug)]
+struct Buyer {
+    secret: i64,
+    prices: Vec<i64>,
+    changes: Vec<i64>,
+}
+
+impl Buyer {
+    fn new(secret: i64, n: usize) -> Buyer {
+        let mut prices = vec![secret % 10];
+        let mut x = secret;
+        for _ in 0..n {
+            x = next(x);
+            prices.push(x % 10);
+        }
+        let changes = prices.windows(2).map(|w| w[1] - w[0]).collect();
+        Buyer {
+            secret,
+            prices,
+            changes,
+        }
+    }
+
+    // Price the monkey sells at for this sequence: the price after its first occurrence
+    fn sale_price(&self, seq: &[i64; 4]) -> Option<i64> {
+        self.changes
+            .windows(4)
+            .position(|w| w == seq)
+            .map(|k| self.prices[k + 4])
+    }
+}
+
+// Total bananas for every sequence, indexed by seq_index
+fn sequence_totals(buyers: &[Buyer]) -> Vec<i64> {
+    let mut seq_sum = vec![0; SEQ_INDEX_MAX];
+    for buyer in buyers {
+        let mut seen = vec![false; SEQ_INDEX_MAX];
+        for (k, w) in buyer.changes.windows(4).enumerate() {
+            let seq_index = seq_index(w);
+            if !seen[seq_index] {
+                seen[seq_index] = true;
+                seq_sum[seq_index] += buyer.prices[k + 4];
+            }
+        }
+    }
+    seq_sum
+}
+
+// k best sequences, best first, ties by sequence order
+fn top_sequences(buyers: &[Buyer], k: usize) -> Vec<([i64; 4], i64)> {
+    let seq_sum = sequence_totals(buyers);
+    let mut ranked: Vec<usize> = (0..SEQ_INDEX_MAX).filter(|&i| seq_sum[i] > 0).collect();
+    ranked.sort_by_key(|&i| (std::cmp::Reverse(seq_sum[i]), i));
+    ranked
+        .into_iter()
+        .take(k)
+        .map(|i| (seq_from_index(i), seq_sum[i]))
+        .collect()
+}
+
+// What each buyer pays for the sequence, None if the sequence never appears
+fn contributions(buyers: &[Buyer], seq: &[i64; 4]) -> Vec<Option<i64>> {
+    buyers.iter().map(|buyer| buyer.sale_price(seq)).collect()
+}
+
+fn series_csv(buyers: &[Buyer]) -> String {
+    let mut out = String::from("secret,index,price,change\n");
+    for buyer in buyers {
+        for (k, price) in buyer.prices.iter().enumerate() {
+            let change = match k {
+                0 => String::new(),
+                _ => buyer.changes[k - 1].to_string(),
+            };
+            out.push_str(&format!("{},{},{},{}\n", buyer.secret, k, price, change));
+        }
+    }
+    out
+}
+
+fn top_sequences_csv(top: &[([i64; 4], i64)]) -> String {
+    let mut out = String::from("rank,c1,c2,c3,c4,total\n");
+    for (rank, (seq, total)) in top.iter().enumerate() {
+        out.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            rank + 1,
+            seq[0],
+            seq[1],
+            seq[2],
+            seq[3],
+            total
+        ));
+    }
+    out
+}
+
+fn contributions_csv(buyers: &[Buyer], seq: &[i64; 4]) -> String {
+    let mut out = String::from("secret,price\n");
+    for (buyer, price) in buyers.iter().zip(contributions(buyers, seq)) {
+        let price = price.map(|p| p.to_string()).unwrap_or_default();
+        out.push_str(&format!("{},{}\n", buyer.secret, price));
+    }
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -103,4 +224,31 @@ mod tests {
         let input = vec![1, 2, 3, 2024];
         assert_eq!(part2(&input), 23);
     }
+
+    #[test]
+    fn test_analytics() {
+        let buyer = Buyer::new(123, 9);
+        assert_eq!(buyer.prices, vec![3, 0, 6, 5, 4, 4, 6, 4, 4, 2]);
+        assert_eq!(buyer.changes, vec![-3, 6, -1, -1, 0, 2, -2, 0, -2]);
+        assert_eq!(buyer.sale_price(&[-1, -1, 0, 2]), Some(6));
+
+        let buyers: Vec<Buyer> = [1, 2, 3, 2024]
+            .iter()
+            .map(|&x| Buyer::new(x, 2000))
+            .collect();
+        let top = top_sequences(&buyers, 3);
+        assert_eq!(top.len(), 3);
+        assert_eq!(top[0], ([-2, 1, -1, 3], 23));
+        assert!(top[1].1 <= 23 && top[2].1 <= top[1].1);
+        assert_eq!(
+            contributions(&buyers, &[-2, 1, -1, 3]),
+            vec![Some(7), Some(7), None, Some(9)]
+        );
+        assert_eq!(
+            contributions_csv(&buyers, &[-2, 1, -1, 3]),
+            "secret,price\n1,7\n2,7\n3,\n2024,9\n"
+        );
+        assert!(top_sequences_csv(&top).starts_with("rank,c1,c2,c3,c4,total\n1,-2,1,-1,3,23\n"));
+        assert!(series_csv(&buyers[..1]).starts_with("secret,index,price,change\n1,0,1,\n"));
+    }
 }
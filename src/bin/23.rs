@@ -1,4 +1,4 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::BTreeMap;
 
 fn main() {
     let input = parse(include_str!("../../input/23.txt"));
@@ -10,6 +10,25 @@ fn main() {
     let part2 = part2(&input);
     println!("Part 2: {}", part2);
     assert_eq!(part2, "bd,dk,ir,ko,lk,nn,ob,pt,te,tl,uh,wj,yl");
+
+    // `--stats`, `--dot network.dot` and `--graphml network.graphml` to inspect the network
+    let args: Vec<String> = std::env::args().collect();
+    let arg_value = |name: &str| {
+        args.iter()
+            .position(|a| a == name)
+            .map(|k| args.get(k + 1).expect("missing argument value").clone())
+    };
+    let graph = Graph::new(&input);
+    let password = graph.nodes_by_name(&part2);
+    if args.iter().any(|a| a == "--stats") {
+        print!("{}", graph.summary());
+    }
+    if let Some(path) = arg_value("--dot") {
+        std::fs::write(path, graph.to_dot(&input, &password)).unwrap();
+    }
+    if let Some(path) = arg_value("--graphml") {
+        std::fs::write(path, graph.to_graphml(&input, &password)).unwrap();
+    }
 }
 
 type Input = Vec<(String, String)>;
@@ -31,94 +50,389 @@ fn parse(input: &str) -> Input {
 }
 
 fn part1(input: &Input) -> i64 {
-    let mut adj = BTreeMap::new();
-    let mut edges = BTreeSet::new();
-    for (a, b) in input {
-        let e = adj.entry(a).or_insert_with(Vec::new);
-        e.push(b);
-        let e = adj.entry(b).or_insert_with(Vec::new);
-        e.push(a);
-        edges.insert((a, b));
-        edges.insert((b, a));
-    }
-
-    let mut results = BTreeSet::new();
-    for (&k, v) in adj.iter() {
-        if k.chars().nth(0).unwrap() == 't' {
-            for i in 0..v.len() {
-                for j in i + 1..v.len() {
-                    if edges.contains(&(v[i], v[j])) {
-                        // println!("{} {} {}", k, v[i], v[j]);
-                        // sort
-                        let mut v = vec![k.clone(), v[i].clone(), v[j].clone()];
-                        v.sort();
-                        results.insert(v);
-                    }
+    let graph = Graph::new(input);
+    let triangles = graph.k_cliques(3, |v| graph.map_rev[v].starts_with('t'));
+    triangles.len() as i64
+}
+
+fn part2(input: &Input) -> String {
+    let graph = Graph::new(input);
+    let clique = graph.max_clique();
+    let mut group = clique
+        .iter()
+        .map(|&i| graph.map_rev[i].clone())
+        .collect::<Vec<_>>();
+    group.sort();
+    group.join(",")
+}
+
+// fixed size bitset of node indices
+#[derive(Debug, Clone, PartialEq)]
+struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    fn new(n: usize) -> Self {
+        BitSet {
+            words: vec![0; n.div_ceil(64)],
+        }
+    }
+
+    fn insert(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    fn remove(&mut self, i: usize) {
+        self.words[i / 64] &= !(1 << (i % 64));
+    }
+
+    fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    fn intersection(&self, other: &BitSet) -> BitSet {
+        BitSet {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(a, b)| a & b)
+                .collect(),
+        }
+    }
+
+    fn difference(&self, other: &BitSet) -> BitSet {
+        BitSet {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(a, b)| a & !b)
+                .collect(),
+        }
+    }
+
+    fn union(&self, other: &BitSet) -> BitSet {
+        BitSet {
+            words: self
+                .words
+                .iter()
+                .zip(&other.words)
+                .map(|(a, b)| a | b)
+                .collect(),
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(k, &w)| {
+            let mut w = w;
+            std::iter::from_fn(move || {
+                if w == 0 {
+                    return None;
                 }
+                let i = w.trailing_zeros() as usize;
+                w &= w - 1;
+                Some(k * 64 + i)
+            })
+        })
+    }
+}
+
+// computers numbered in order of first appearance
+struct Graph {
+    map: BTreeMap<String, usize>,
+    map_rev: Vec<String>,
+    adj: Vec<BitSet>,
+}
+
+impl Graph {
+    fn new(input: &Input) -> Self {
+        // enumerate all computers
+        let mut map = BTreeMap::new();
+        let mut map_rev = vec![];
+        for (a, b) in input {
+            for name in [a, b] {
+                if !map.contains_key(name) {
+                    map.insert(name.clone(), map_rev.len());
+                    map_rev.push(name.clone());
+                }
+            }
+        }
+
+        let count = map_rev.len();
+        let mut adj = vec![BitSet::new(count); count];
+        for (a, b) in input {
+            let a = map[a];
+            let b = map[b];
+            adj[a].insert(b);
+            adj[b].insert(a);
+        }
+        Graph { map, map_rev, adj }
+    }
+
+    fn len(&self) -> usize {
+        self.map_rev.len()
+    }
+
+    fn all_nodes(&self) -> BitSet {
+        let mut all = BitSet::new(self.len());
+        for v in 0..self.len() {
+            all.insert(v);
+        }
+        all
+    }
+
+    // every k-clique with at least one node matching the predicate, each once
+    fn k_cliques(&self, k: usize, pred: impl Fn(usize) -> bool) -> Vec<Vec<usize>> {
+        let mut cliques = vec![];
+        let mut clique = vec![];
+        self.extend_clique(k, &mut clique, self.all_nodes(), &pred, &mut cliques);
+        cliques
+    }
+
+    // Grow the clique with candidates larger than its last node, so each clique is built in one order only
+    fn extend_clique(
+        &self,
+        k: usize,
+        clique: &mut Vec<usize>,
+        candidates: BitSet,
+        pred: &impl Fn(usize) -> bool,
+        cliques: &mut Vec<Vec<usize>>,
+    ) {
+        if clique.len() == k {
+            if clique.iter().any(|&v| pred(v)) {
+                cliques.push(clique.clone());
+            }
+            return;
+        }
+        if clique.len() + candidates.len() < k {
+            return;
+        }
+        let mut candidates = candidates;
+        for v in candidates.clone().iter() {
+            candidates.remove(v);
+            clique.push(v);
+            self.extend_clique(
+                k,
+                clique,
+                candidates.intersection(&self.adj[v]),
+                pred,
+                cliques,
+            );
+            clique.pop();
+        }
+    }
+
+    // Nodes in degeneracy order: repeatedly take a node of smallest remaining degree
+    fn degeneracy_order(&self) -> Vec<usize> {
+        let mut degree: Vec<usize> = self.adj.iter().map(|a| a.len()).collect();
+        let mut removed = vec![false; self.len()];
+        let mut order = vec![];
+        for _ in 0..self.len() {
+            let v = (0..self.len())
+                .filter(|&v| !removed[v])
+                .min_by_key(|&v| degree[v])
+                .unwrap();
+            removed[v] = true;
+            order.push(v);
+            for u in self.adj[v].iter() {
+                degree[u] -= 1;
             }
         }
+        order
+    }
+
+    // bron-kerbosch with pivoting, outer level in degeneracy order
+    fn maximal_cliques(&self, report: &mut impl FnMut(&[usize])) {
+        let mut p = self.all_nodes();
+        let mut x = BitSet::new(self.len());
+        for v in self.degeneracy_order() {
+            let mut r = vec![v];
+            self.bron_kerbosch(
+                &mut r,
+                p.intersection(&self.adj[v]),
+                x.intersection(&self.adj[v]),
+                report,
+            );
+            p.remove(v);
+            x.insert(v);
+        }
+    }
+
+    fn bron_kerbosch(
+        &self,
+        r: &mut Vec<usize>,
+        p: BitSet,
+        x: BitSet,
+        report: &mut impl FnMut(&[usize]),
+    ) {
+        if p.is_empty() {
+            if x.is_empty() {
+                report(r);
+            }
+            return;
+        }
+        // pivot on the node with the most neighbours in p, then only branch on p minus its neighbours
+        let pivot = p
+            .union(&x)
+            .iter()
+            .max_by_key(|&u| p.intersection(&self.adj[u]).len())
+            .unwrap();
+        let mut p = p;
+        let mut x = x;
+        for v in p.difference(&self.adj[pivot]).iter() {
+            r.push(v);
+            self.bron_kerbosch(
+                r,
+                p.intersection(&self.adj[v]),
+                x.intersection(&self.adj[v]),
+                report,
+            );
+            r.pop();
+            p.remove(v);
+            x.insert(v);
+        }
+    }
+
+    fn max_clique(&self) -> Vec<usize> {
+        let mut best = vec![];
+        self.maximal_cliques(&mut |clique| {
+            if clique.len() > best.len() {
+                best = clique.to_vec();
+            }
+        });
+        best
     }
-    // results.iter().for_each(|v| println!("{:?}", v));
-    results.len() as i64
 }
 
-fn part2(input: &Input) -> String {
-    // enumerate all computers
-    let mut map = BTreeMap::new();
-    let mut map_rev = BTreeMap::new();
-    let mut count = 0;
-    for (a, b) in input {
-        if !map.contains_key(a) {
-            map.insert(a, count);
-            map_rev.insert(count, a);
-            count += 1;
-        }
-        if !map.contains_key(b) {
-            map.insert(b, count);
-            map_rev.insert(count, b);
-            count += 1;
-        }
-    }
-
-    // Make adjacency matrix
-    let mut matrix = vec![vec![false; count]; count];
-    for (a, b) in input {
-        let a = map[a];
-        let b = map[b];
-        matrix[a][b] = true;
-        matrix[b][a] = true;
-    }
-
-    let mut groups = vec![];
-    for i in 0..count {
-        groups.push(vec![i]);
-    }
-    for group_sz in 2.. {
-        let mut groups1 = vec![];
-        for group in &groups {
-            let last = group[group_sz - 2];
-            for i in last + 1..count {
-                if group.iter().all(|&j| matrix[i][j]) {
-                    let mut new_group = group.clone();
-                    new_group.push(i);
-                    groups1.push(new_group);
+impl Graph {
+    // Number of computers with each degree
+    fn degree_distribution(&self) -> BTreeMap<usize, usize> {
+        let mut counts = BTreeMap::new();
+        for a in &self.adj {
+            *counts.entry(a.len()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    // Connected components, largest first
+    fn components(&self) -> Vec<Vec<usize>> {
+        let mut seen = vec![false; self.len()];
+        let mut components = vec![];
+        for v in 0..self.len() {
+            if seen[v] {
+                continue;
+            }
+            seen[v] = true;
+            let mut component = vec![];
+            let mut stack = vec![v];
+            while let Some(u) = stack.pop() {
+                component.push(u);
+                for w in self.adj[u].iter() {
+                    if !seen[w] {
+                        seen[w] = true;
+                        stack.push(w);
+                    }
                 }
             }
+            component.sort();
+            components.push(component);
         }
-        groups = groups1;
-        println!("{} groups of size {}", groups.len(), group_sz);
-        if groups.len() <= 1 {
-            break;
+        components.sort_by_key(|c| std::cmp::Reverse(c.len()));
+        components
+    }
+
+    fn clique_number(&self) -> usize {
+        self.max_clique().len()
+    }
+
+    fn summary(&self) -> String {
+        let components = self.components();
+        let sizes: Vec<String> = components.iter().map(|c| c.len().to_string()).collect();
+        let mut out = format!("computers: {}\n", self.len());
+        out.push_str(&format!(
+            "connections: {}\n",
+            self.adj.iter().map(|a| a.len()).sum::<usize>() / 2
+        ));
+        out.push_str(&format!(
+            "components: {} (sizes {})\n",
+            components.len(),
+            sizes.join(", ")
+        ));
+        out.push_str(&format!("clique number: {}\n", self.clique_number()));
+        out.push_str("degree distribution:\n");
+        for (degree, count) in self.degree_distribution() {
+            out.push_str(&format!("  {}: {}\n", degree, count));
         }
+        out
     }
 
-    let group = groups.get(0).unwrap();
-    let mut group = group
-        .iter()
-        .map(|&i| map_rev[&i].clone())
-        .collect::<Vec<_>>();
-    group.sort();
-    group.join(",")
+    // Node indices for a comma separated list of names, like the part 2 password
+    fn nodes_by_name(&self, names: &str) -> Vec<usize> {
+        names.split(',').map(|name| self.map[name]).collect()
+    }
+
+    // graphviz, highlighted nodes and edges in red
+    fn to_dot(&self, input: &Input, highlight: &[usize]) -> String {
+        let mut out = String::from("graph network {\n");
+        for (v, name) in self.map_rev.iter().enumerate() {
+            if highlight.contains(&v) {
+                out.push_str(&format!("  \"{}\" [style=filled, fillcolor=red];\n", name));
+            } else {
+                out.push_str(&format!("  \"{}\";\n", name));
+            }
+        }
+        for (a, b) in input {
+            if highlight.contains(&self.map[a]) && highlight.contains(&self.map[b]) {
+                out.push_str(&format!(
+                    "  \"{}\" -- \"{}\" [color=red, penwidth=2];\n",
+                    a, b
+                ));
+            } else {
+                out.push_str(&format!("  \"{}\" -- \"{}\";\n", a, b));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    // graphml, with a boolean `highlight` attribute
+    fn to_graphml(&self, input: &Input, highlight: &[usize]) -> String {
+        let mut out = String::from(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="highlight" for="all" attr.name="highlight" attr.type="boolean"><default>false</default></key>
+  <graph id="network" edgedefault="undirected">
+"#,
+        );
+        for (v, name) in self.map_rev.iter().enumerate() {
+            if highlight.contains(&v) {
+                out.push_str(&format!(
+                    "    <node id=\"{}\"><data key=\"highlight\">true</data></node>\n",
+                    name
+                ));
+            } else {
+                out.push_str(&format!("    <node id=\"{}\"/>\n", name));
+            }
+        }
+        for (a, b) in input {
+            if highlight.contains(&self.map[a]) && highlight.contains(&self.map[b]) {
+                out.push_str(&format!(
+                    "    <edge source=\"{}\" target=\"{}\"><data key=\"highlight\">true</data></edge>\n",
+                    a, b
+                ));
+            } else {
+                out.push_str(&format!("    <edge source=\"{}\" target=\"{}\"/>\n", a, b));
+            }
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
 }
 
 #[cfg(test)]
@@ -131,4 +445,71 @@ mod tests {
         assert_eq!(part1(&input), 7);
         assert_eq!(part2(&input), "co,de,ka,ta");
     }
+
+    #[test]
+    fn test_stats() {
+        let input = parse(include_str!("../../input/23_example.txt"));
+        let graph = Graph::new(&input);
+        assert_eq!(graph.len(), 16);
+        assert_eq!(graph.degree_distribution(), BTreeMap::from([(4, 16)]));
+        assert_eq!(graph.components().len(), 1);
+        assert_eq!(graph.clique_number(), 4);
+        assert!(graph.summary().contains("components: 1 (sizes 16)"));
+
+        let password = graph.nodes_by_name("co,de,ka,ta");
+        let dot = graph.to_dot(&input, &password);
+        assert!(dot.starts_with("graph network {\n"));
+        assert!(dot.contains("\"co\" [style=filled, fillcolor=red];"));
+        assert!(dot.contains("\"ka\" -- \"co\" [color=red, penwidth=2];"));
+        assert!(dot.contains("\"kh\" -- \"tc\";"));
+        assert_eq!(dot.matches("penwidth").count(), 6);
+        let graphml = graph.to_graphml(&input, &password);
+        assert_eq!(graphml.matches("<node ").count(), 16);
+        assert_eq!(graphml.matches("<edge ").count(), input.len());
+        assert_eq!(graphml.matches(">true<").count(), 4 + 6);
+
+        // two separate triangles and a lone edge
+        let input = parse("a-b\nb-c\nc-a\nd-e\ne-f\nf-d\ng-h\n");
+        let graph = Graph::new(&input);
+        let sizes: Vec<usize> = graph.components().iter().map(|c| c.len()).collect();
+        assert_eq!(sizes, vec![3, 3, 2]);
+        assert_eq!(
+            graph.degree_distribution(),
+            BTreeMap::from([(1, 2), (2, 6)])
+        );
+    }
+
+    #[test]
+    fn test_cliques() {
+        let input = parse(include_str!("../../input/23_example.txt"));
+        let graph = Graph::new(&input);
+        assert_eq!(graph.k_cliques(3, |_| true).len(), 12);
+        assert_eq!(graph.k_cliques(4, |_| true).len(), 1);
+        assert_eq!(graph.k_cliques(5, |_| true).len(), 0);
+
+        let mut maximal = vec![];
+        graph.maximal_cliques(&mut |clique| maximal.push(clique.to_vec()));
+        assert_eq!(maximal.iter().map(|c| c.len()).max(), Some(4));
+        for clique in &maximal {
+            // a clique, and no other node is connected to all of it
+            for &a in clique {
+                assert!(clique
+                    .iter()
+                    .all(|&b| a == b || graph.adj[a].iter().any(|c| c == b)));
+            }
+            let common = clique
+                .iter()
+                .fold(graph.all_nodes(), |acc, &v| acc.intersection(&graph.adj[v]));
+            assert!(common.is_empty());
+        }
+
+        let mut set = BitSet::new(130);
+        for i in [0, 63, 64, 129] {
+            set.insert(i);
+        }
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![0, 63, 64, 129]);
+        assert_eq!(set.len(), 4);
+        set.remove(64);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![0, 63, 129]);
+    }
 }
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 fn main() {
     let input = parse(include_str!("../../input/25.txt"));
 
@@ -5,6 +7,17 @@ fn main() {
     println!("Part 1: {}", part1);
     assert_eq!(part1, 2770);
 
+    let inventory = Inventory::new(&input).unwrap();
+    assert_eq!(inventory.count_fits_pairs(), part1);
+    assert_eq!(inventory.count_fits_buckets(), part1);
+
+    // e.g. `--report fits.txt` to list which keys open which locks
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(k) = args.iter().position(|a| a == "--report") {
+        let path = args.get(k + 1).expect("missing argument value");
+        std::fs::write(path, inventory.report()).unwrap();
+    }
+
     let part2 = part2(&input);
     println!("Part 2: {}", part2);
     assert_eq!(part2, 0);
@@ -22,62 +35,233 @@ fn parse(input: &str) -> Input {
                 .map(|line| line.chars().collect())
                 .collect()
         })
+        .filter(|schematic: &Vec<Vec<char>>| !schematic.is_empty())
         .collect();
+    // any size, as long as every schematic is the same size
     for schematic in &i {
-        assert_eq!(schematic.len(), 7);
+        assert_eq!(schematic.len(), i[0].len());
         for line in schematic {
-            assert_eq!(line.len(), 5);
+            assert_eq!(line.len(), i[0][0].len());
         }
     }
     i
 }
 
-fn part1(input: &Input) -> i64 {
-    // sort out keys and locks, and convert to heights
-    let mut keys = vec![];
-    let mut locks = vec![];
-    for schematic in input {
-        if schematic[0][0] == '#' {
-            let mut lock = vec![-1; 5];
-            for j in 0..5 {
-                let mut i = 0;
-                while schematic[i + 1][j] == '#' {
-                    i += 1;
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Lock,
+    Key,
+}
+
+// pin heights, down from the top for a lock, up from the bottom for a key
+#[derive(Debug, Clone, PartialEq)]
+struct Schematic {
+    kind: Kind,
+    heights: Vec<usize>,
+}
+
+impl Schematic {
+    // A lock has a full top row and each column is a run of '#' down from it, then only '.'. Keys are the same upside down
+    fn new(grid: &[Vec<char>]) -> Result<Schematic, String> {
+        let rows = grid.len();
+        if rows < 2 {
+            return Err(format!("schematic has {} rows, need at least 2", rows));
+        }
+        let full = |row: &[char]| row.iter().all(|&c| c == '#');
+        let empty = |row: &[char]| row.iter().all(|&c| c == '.');
+        let kind = if full(&grid[0]) && empty(&grid[rows - 1]) {
+            Kind::Lock
+        } else if empty(&grid[0]) && full(&grid[rows - 1]) {
+            Kind::Key
+        } else {
+            return Err("neither a lock nor a key".to_string());
+        };
+
+        let mut heights = vec![];
+        for j in 0..grid[0].len() {
+            let column: Vec<char> = match kind {
+                Kind::Lock => grid.iter().map(|row| row[j]).collect(),
+                Kind::Key => grid.iter().rev().map(|row| row[j]).collect(),
+            };
+            let pins = column.iter().take_while(|&&c| c == '#').count();
+            if column[pins..].iter().any(|&c| c != '.') {
+                return Err(format!("column {} is not a contiguous pin", j));
+            }
+            heights.push(pins - 1);
+        }
+        Ok(Schematic { kind, heights })
+    }
+}
+
+// space is the number of rows between the top and bottom
+#[derive(Debug)]
+struct Inventory {
+    space: usize,
+    locks: Vec<Vec<usize>>,
+    keys: Vec<Vec<usize>>,
+}
+
+impl Inventory {
+    fn new(input: &Input) -> Result<Inventory, String> {
+        let mut locks = vec![];
+        let mut keys = vec![];
+        for (k, grid) in input.iter().enumerate() {
+            let schematic = Schematic::new(grid).map_err(|e| format!("schematic {}: {}", k, e))?;
+            match schematic.kind {
+                Kind::Lock => locks.push(schematic.heights),
+                Kind::Key => keys.push(schematic.heights),
+            }
+        }
+        // every schematic has at least 2 rows by now
+        let space = input.first().map_or(0, |schematic| schematic.len() - 2);
+        Ok(Inventory { space, locks, keys })
+    }
+
+    fn width(&self) -> usize {
+        self.locks
+            .iter()
+            .chain(&self.keys)
+            .next()
+            .map_or(0, |heights| heights.len())
+    }
+
+    fn fits(&self, lock: &[usize], key: &[usize]) -> bool {
+        lock.iter().zip(key).all(|(l, k)| l + k <= self.space)
+    }
+
+    // Try every lock with every key
+    fn count_fits_pairs(&self) -> i64 {
+        let mut count = 0;
+        for lock in &self.locks {
+            for key in &self.keys {
+                if self.fits(lock, key) {
+                    count += 1;
                 }
-                lock[j] = i as i64;
             }
-            locks.push(lock);
-        } else {
-            let mut key = vec![-1; 5];
-            for j in 0..5 {
-                let mut i = 0;
-                while schematic[5 - i][j] == '#' {
-                    i += 1;
+        }
+        count
+    }
+
+    // count keys at or below every height profile, then a lock fits the keys
+    // at or below space - height, one lookup per lock
+    fn count_fits(&self) -> i64 {
+        let base = self.space + 1;
+        let width = self.width() as u32;
+        let buckets = match base.checked_pow(width) {
+            Some(n) if n <= 1 << 22 => n,
+            _ => return self.count_fits_buckets(),
+        };
+        let index = |heights: &[usize]| heights.iter().fold(0, |acc, h| acc * base + h);
+
+        let mut at_or_below = vec![0i64; buckets];
+        for key in &self.keys {
+            at_or_below[index(key)] += 1;
+        }
+        // prefix sums one column at a time
+        let mut stride = 1;
+        for _ in 0..width {
+            for i in 0..buckets {
+                if (i / stride) % base != 0 {
+                    at_or_below[i] += at_or_below[i - stride];
                 }
-                key[j] = i as i64;
             }
-            keys.push(key);
+            stride *= base;
         }
+
+        self.locks
+            .iter()
+            .map(|lock| {
+                let complement: Vec<usize> = lock.iter().map(|h| self.space - h).collect();
+                at_or_below[index(&complement)]
+            })
+            .sum()
+    }
+
+    // Columns where the key and lock pins overlap, and by how many rows
+    fn overlaps(&self, lock: &[usize], key: &[usize]) -> Vec<(usize, usize)> {
+        lock.iter()
+            .zip(key)
+            .enumerate()
+            .filter(|(_, (l, k))| *l + *k > self.space)
+            .map(|(j, (l, k))| (j, l + k - self.space))
+            .collect()
     }
-    // println!("{:?}", locks);
-    // println!("{:?}", keys);
-
-    let mut count = 0;
-    for key in &keys {
-        for lock in &locks {
-            let mut fit = true;
-            for j in 0..5 {
-                if key[j] + lock[j] > 5 {
-                    fit = false;
-                    break;
+
+    // every lock/key pair side by side, with whether they fit or where they overlap
+    fn report(&self) -> String {
+        let mut out = String::new();
+        for (l, lock) in self.locks.iter().enumerate() {
+            for (k, key) in self.keys.iter().enumerate() {
+                let overlaps = self.overlaps(lock, key);
+                let verdict = if overlaps.is_empty() {
+                    "fit".to_string()
+                } else {
+                    let columns: Vec<String> = overlaps
+                        .iter()
+                        .map(|(j, by)| format!("column {} by {}", j, by))
+                        .collect();
+                    format!("overlap in {}", columns.join(", "))
+                };
+                out.push_str(&format!(
+                    "lock {} {} and key {} {}: {}\n",
+                    l,
+                    join(lock),
+                    k,
+                    join(key),
+                    verdict
+                ));
+                let lock_rows = render(Kind::Lock, lock, self.space);
+                let key_rows = render(Kind::Key, key, self.space);
+                for (lock_row, key_row) in lock_rows.iter().zip(&key_rows) {
+                    out.push_str(&format!("{}  {}\n", lock_row, key_row));
                 }
+                out.push('\n');
             }
-            if fit {
-                count += 1;
+        }
+        out
+    }
+
+    // For schematics too large for a dense table, only compare each lock with the distinct key profiles
+    fn count_fits_buckets(&self) -> i64 {
+        let mut buckets: HashMap<&[usize], i64> = HashMap::new();
+        for key in &self.keys {
+            *buckets.entry(key).or_insert(0) += 1;
+        }
+        let mut count = 0;
+        for lock in &self.locks {
+            for (key, n) in &buckets {
+                if self.fits(lock, key) {
+                    count += n;
+                }
             }
         }
+        count
+    }
+}
+
+fn join(heights: &[usize]) -> String {
+    let heights: Vec<String> = heights.iter().map(|h| h.to_string()).collect();
+    heights.join(",")
+}
+
+// Back to the '#'/'.' schematic, the inverse of Schematic::new
+fn render(kind: Kind, heights: &[usize], space: usize) -> Vec<String> {
+    let mut rows: Vec<String> = (0..space + 2)
+        .map(|i| {
+            heights
+                .iter()
+                .map(|&h| if i <= h { '#' } else { '.' })
+                .collect()
+        })
+        .collect();
+    if kind == Kind::Key {
+        rows.reverse();
     }
-    count
+    rows
+}
+
+fn part1(input: &Input) -> i64 {
+    Inventory::new(input).unwrap().count_fits()
 }
 
 fn part2(input: &Input) -> i64 {
@@ -94,4 +278,111 @@ mod tests {
         assert_eq!(part1(&input), 3);
         assert_eq!(part2(&input), 0);
     }
+
+    #[test]
+    fn test_schematics() {
+        let input = parse(include_str!("../../input/25_example.txt"));
+        let inventory = Inventory::new(&input).unwrap();
+        assert_eq!(inventory.space, 5);
+        assert_eq!(inventory.width(), 5);
+        assert_eq!(
+            inventory.locks,
+            vec![vec![0, 5, 3, 4, 3], vec![1, 2, 0, 5, 3]]
+        );
+        assert_eq!(
+            inventory.keys,
+            vec![
+                vec![5, 0, 2, 1, 3],
+                vec![4, 3, 4, 0, 2],
+                vec![3, 0, 2, 0, 1]
+            ]
+        );
+        assert_eq!(inventory.count_fits_pairs(), 3);
+        assert_eq!(inventory.count_fits_buckets(), 3);
+
+        // 3 wide with room for 2 pins
+        let input = parse("###\n#.#\n...\n...\n\n...\n...\n.#.\n###\n\n...\n#..\n#.#\n###\n");
+        let inventory = Inventory::new(&input).unwrap();
+        assert_eq!(inventory.space, 2);
+        assert_eq!(inventory.locks, vec![vec![1, 0, 1]]);
+        assert_eq!(inventory.keys, vec![vec![0, 1, 0], vec![2, 0, 1]]);
+        assert_eq!(inventory.count_fits(), 1);
+
+        let error = |schematic: &str| Inventory::new(&parse(schematic)).unwrap_err();
+        assert_eq!(
+            error("#####\n"),
+            "schematic 0: schematic has 1 rows, need at least 2"
+        );
+        assert_eq!(
+            error("###\n#..\n..#\n...\n"),
+            "schematic 0: column 2 is not a contiguous pin"
+        );
+        assert_eq!(
+            error("###\n.#.\n###\n"),
+            "schematic 0: neither a lock nor a key"
+        );
+        assert_eq!(
+            error("...\n#..\n...\n###\n"),
+            "schematic 0: column 0 is not a contiguous pin"
+        );
+
+        // render is the inverse of parsing
+        let input = parse(include_str!("../../input/25_example.txt"));
+        for grid in &input {
+            let schematic = Schematic::new(grid).unwrap();
+            let rows: Vec<String> = grid.iter().map(|row| row.iter().collect()).collect();
+            assert_eq!(render(schematic.kind, &schematic.heights, 5), rows);
+        }
+
+        // dense table and plain buckets agree with every pair on all keys and locks 3 wide with room for 3
+        let mut profiles = vec![];
+        for p in 0..64 {
+            profiles.push(vec![p % 4, (p / 4) % 4, p / 16]);
+        }
+        let inventory = Inventory {
+            space: 3,
+            locks: profiles.iter().step_by(3).cloned().collect(),
+            keys: profiles.iter().chain(&profiles[..10]).cloned().collect(),
+        };
+        assert_eq!(inventory.count_fits(), inventory.count_fits_pairs());
+        assert_eq!(inventory.count_fits_buckets(), inventory.count_fits_pairs());
+    }
+
+    #[test]
+    fn test_report() {
+        let input = parse(include_str!("../../input/25_example.txt"));
+        let inventory = Inventory::new(&input).unwrap();
+        assert_eq!(
+            inventory.overlaps(&[0, 5, 3, 4, 3], &[5, 0, 2, 1, 3]),
+            vec![(4, 1)]
+        );
+        assert_eq!(
+            inventory.overlaps(&[0, 5, 3, 4, 3], &[4, 3, 4, 0, 2]),
+            vec![(1, 3), (2, 2)]
+        );
+        assert_eq!(
+            inventory.overlaps(&[0, 5, 3, 4, 3], &[3, 0, 2, 0, 1]),
+            vec![]
+        );
+
+        let report = inventory.report();
+        assert_eq!(report.matches(": fit\n").count(), 3);
+        assert_eq!(report.lines().filter(|l| l.starts_with("lock ")).count(), 6);
+        let first = "\
+lock 0 0,5,3,4,3 and key 0 5,0,2,1,3: overlap in column 4 by 1
+#####  .....
+.####  #....
+.####  #....
+.####  #...#
+.#.#.  #.#.#
+.#...  #.###
+.....  #####
+
+";
+        assert!(report.starts_with(first));
+        assert!(report.contains(
+            "lock 1 1,2,0,5,3 and key 0 5,0,2,1,3: overlap in column 0 by 1, column 3 by 1, column 4 by 1\n"
+        ));
+        assert!(report.contains("lock 0 0,5,3,4,3 and key 2 3,0,2,0,1: fit\n"));
+    }
 }